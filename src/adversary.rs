@@ -1,25 +1,367 @@
 use rand::Rng;
-use crate::config::{attiva_avversario, lung_msg, POL_X, POL_Z};
+use crate::bit_vector::{SequenzaFotoni, VettoreBit};
+use crate::config::{
+    attiva_avversario, attiva_rivelatore_avversario, base_casuale, base_da_bit, bit_da_base, fedelta_clonazione, frazione_attacco, lung_msg, protocollo, strategia_avversario, Protocollo,
+    StrategiaAvversario, POL_X, POL_Z,
+};
+use crate::detector::Detector;
+use crate::entangled_source::EntangledSource;
+use crate::information::informazione_mutua_bsc;
+use crate::privacy_amplification::comprimi_chiave;
+use crate::public_channel::PublicChannel;
 use crate::quantum_channel::QuantumChannel;
+use crate::reader::Reader;
+use crate::writer::Writer;
+use crate::session_rng::generatore;
 
-/// Avversario (opzionale): legge i fotoni sempre prima del lettore se attivato.
+/// Avversario (opzionale): agisce sui fotoni sempre prima del lettore se attivato.
 pub struct Adversary {
-    pub avversario_messaggio_quantistico_ricevuto: Vec<(char, u8)>,
+    // Misure per slot, significative solo dove `slot_misurati` è true (gli altri valgono (Z, 0))
+    pub avversario_messaggio_quantistico_ricevuto: SequenzaFotoni,
+    pub slot_misurati: VettoreBit,
+    // Memoria quantistica: (slot, fotone) trattenuti, misurati solo dopo l'annuncio delle basi
+    pub memoria_quantistica: Vec<(usize, QuantumChannel)>,
+    pub impulsi_bloccati: usize,
+    pub impulsi_attaccati: usize,
+    pub basi_sondate: usize, // cavallo di Troia: impulsi di cui la sonda ha rivelato la base
+    pub rivelatore: Option<Detector>, // None: lettura ideale
+}
+
+/// Conoscenza dell'avversario sulle chiavi di una sessione: accordo bit a bit della sua stima
+/// con la chiave dello scrittore, confrontato con quello del lettore, prima e dopo l'amplificazione della privacy
+#[derive(Debug, Clone, Default)]
+pub struct ConoscenzaAvversario {
+    pub bit_grezzi: usize,
+    pub accordo_grezza_lettore: usize,     // bit della chiave grezza uguali tra scrittore e lettore
+    pub accordo_grezza_avversario: usize,  // bit della chiave grezza indovinati dall'avversario
+    pub bit_chiave: usize,                 // bit della chiave prima della compressione (senza test)
+    pub accordo_chiave_avversario: usize,
+    pub bit_finali: usize,                 // bit della chiave finale (0 se la sessione non ha prodotto chiave)
+    pub accordo_finale_avversario: usize,  // bit della chiave finale indovinati applicando la stessa compressione
+    pub amplificazione: bool,              // true se la chiave finale è stata compressa
+}
+
+impl ConoscenzaAvversario {
+    /// Somma i conteggi di un'altra sessione (statistiche di batch)
+    pub fn aggiungi(&mut self, altra: &ConoscenzaAvversario) {
+        self.bit_grezzi += altra.bit_grezzi;
+        self.accordo_grezza_lettore += altra.accordo_grezza_lettore;
+        self.accordo_grezza_avversario += altra.accordo_grezza_avversario;
+        self.bit_chiave += altra.bit_chiave;
+        self.accordo_chiave_avversario += altra.accordo_chiave_avversario;
+        self.bit_finali += altra.bit_finali;
+        self.accordo_finale_avversario += altra.accordo_finale_avversario;
+        self.amplificazione |= altra.amplificazione;
+    }
+
+    fn frazione(accordo: usize, totale: usize) -> f64 {
+        if totale == 0 { 0.0 } else { accordo as f64 / totale as f64 }
+    }
+
+    /// I(A:B) per bit della chiave grezza, stimata dal tasso di disaccordo scrittore-lettore
+    pub fn informazione_ab(&self) -> f64 {
+        if self.bit_grezzi == 0 { return 0.0; }
+        informazione_mutua_bsc(1.0 - Self::frazione(self.accordo_grezza_lettore, self.bit_grezzi))
+    }
+
+    /// I(A:E) per bit della chiave grezza, stimata dal tasso di disaccordo scrittore-avversario
+    pub fn informazione_ae(&self) -> f64 {
+        if self.bit_grezzi == 0 { return 0.0; }
+        informazione_mutua_bsc(1.0 - Self::frazione(self.accordo_grezza_avversario, self.bit_grezzi))
+    }
+
+    /// I(A:E) per bit della chiave finale: ciò che resta della conoscenza dopo l'amplificazione della privacy
+    pub fn informazione_ae_finale(&self) -> f64 {
+        if self.bit_finali == 0 { return 0.0; }
+        informazione_mutua_bsc(1.0 - Self::frazione(self.accordo_finale_avversario, self.bit_finali))
+    }
 }
 
 impl Adversary {
     pub fn new() -> Self {
-        Self { avversario_messaggio_quantistico_ricevuto: Vec::with_capacity(lung_msg()) }
+        Self {
+            avversario_messaggio_quantistico_ricevuto: SequenzaFotoni::con_capacita(lung_msg()),
+            slot_misurati: VettoreBit::con_capacita(lung_msg()),
+            memoria_quantistica: Vec::new(),
+            impulsi_bloccati: 0,
+            impulsi_attaccati: 0,
+            basi_sondate: 0,
+            rivelatore: if attiva_rivelatore_avversario() { Some(Detector::da_config()) } else { None },
+        }
     }
 
-    /// Lettura di un fotone (se ATTIVA_AVVERSARIO=true). Deve avvenire prima del lettore.
+    /// Registra l'esito dell'avversario sullo slot corrente (None: nessuna misura)
+    fn registra(&mut self, misura: Option<(char, u8)>) {
+        self.avversario_messaggio_quantistico_ricevuto.push(misura.unwrap_or((POL_Z, 0)));
+        self.slot_misurati.push(misura.is_some());
+    }
+
+    /// Trattiene in memoria il fotone dello slot appena registrato
+    fn trattieni(&mut self, fotone: QuantumChannel) {
+        self.memoria_quantistica.push((self.slot_misurati.len() - 1, fotone));
+    }
+
+    /// Misura dell'avversario sullo slot `i`, se presente
+    pub fn misura(&self, i: usize) -> Option<(char, u8)> {
+        self.slot_misurati.get(i)?.then(|| self.avversario_messaggio_quantistico_ricevuto[i])
+    }
+
+    /// Cavallo di Troia: sonda l'apparato dello scrittore mentre prepara l'impulso `indice` e,
+    /// se ha letto la base, misura il fotone in quella base senza disturbarlo.
+    /// Gli impulsi di cui non conosce la base proseguono intatti.
+    pub fn sonda_e_leggi(&mut self, scr: &mut Writer, indice: usize, q: &mut QuantumChannel) {
+        if !q.fotone_presente() || !generatore().gen_bool(frazione_attacco()) {
+            self.registra(None);
+            return;
+        }
+        self.impulsi_attaccati += 1;
+        match scr.risposta_sonda(indice) {
+            Some(pol) => {
+                self.basi_sondate += 1;
+                let valore = q.lettura_fotone(pol);
+                self.registra(Some((pol, valore)));
+            }
+            None => self.registra(None),
+        }
+    }
+
+    /// Azione sul fotone (se ATTIVA_AVVERSARIO=true) secondo la strategia configurata.
+    /// Deve avvenire prima del lettore. Gli impulsi vuoti non possono essere letti: lo slot resta senza misura.
+    /// Con `FRAZIONE_ATTACCO` < 1 solo una parte casuale degli impulsi viene attaccata, gli altri proseguono indisturbati.
+    /// Il cavallo di Troia agisce sull'apparato dello scrittore (`sonda_e_leggi`), non sul canale:
+    /// qui l'impulso prosegue intatto e lo slot resta senza misura.
     pub fn leggi_fotone_se_attivo(&mut self, q: &mut QuantumChannel) {
         if attiva_avversario() {
-            let mut rng = rand::thread_rng();
-            let pol_let = if rng.gen_bool(0.5) { POL_Z } else { POL_X };
-            let valore = q.lettura_fotone(pol_let);
-            self.avversario_messaggio_quantistico_ricevuto.push((pol_let, valore));
+            let strategia = strategia_avversario();
+            if strategia == StrategiaAvversario::CavalloDiTroia || !q.fotone_presente() || !generatore().gen_bool(frazione_attacco()) {
+                self.registra(None);
+                return;
+            }
+            self.impulsi_attaccati += 1;
+            match strategia {
+                StrategiaAvversario::IntercettaRispedisci => self.intercetta_e_rispedisci(q),
+                StrategiaAvversario::Pns => self.separa_fotone_pns(q),
+                StrategiaAvversario::Clonazione => self.clona_fotone(q),
+                StrategiaAvversario::Memoria => self.memorizza_e_sostituisci(q),
+                StrategiaAvversario::Accecamento => self.accecamento_stato_forzato(q),
+                StrategiaAvversario::CavalloDiTroia => {}
+            }
             // NOTA: l'avversario **non** resetta Fotone_IN; il reset è responsabilità del lettore
         }
     }
+
+    /// Intercetta e rispedisci: misura in una base casuale; il fotone collassato prosegue verso il lettore.
+    /// Con il modello di rivelatore, se nessun rivelatore scatta l'avversario non ha nulla da rispedire
+    /// e blocca l'impulso; altrimenti rispedisce un singolo fotone.
+    fn intercetta_e_rispedisci(&mut self, q: &mut QuantumChannel) {
+        let pol_let = base_casuale();
+        match &mut self.rivelatore {
+            Some(rivelatore) => match rivelatore.misura(q, pol_let) {
+                Some(valore) => {
+                    q.spedizione_fotone(q.canale_quantistico.0, valore);
+                    self.registra(Some((pol_let, valore)));
+                }
+                None => {
+                    q.numero_fotoni = 0;
+                    self.registra(None);
+                }
+            },
+            None => {
+                let valore = q.lettura_fotone(pol_let);
+                self.registra(Some((pol_let, valore)));
+            }
+        }
+    }
+
+    /// Photon-number-splitting: gli impulsi a singolo fotone vengono bloccati (il lettore riceve un impulso vuoto),
+    /// dagli impulsi multi-fotone viene sottratto un fotone e conservato in memoria senza disturbare gli altri.
+    /// Lo slot resta senza misura finché la memoria non viene misurata.
+    fn separa_fotone_pns(&mut self, q: &mut QuantumChannel) {
+        self.registra(None);
+        if q.numero_fotoni == 1 {
+            q.numero_fotoni = 0;
+            self.impulsi_bloccati += 1;
+        } else {
+            let mut fotone = q.clone();
+            fotone.numero_fotoni = 1;
+            q.numero_fotoni -= 1;
+            self.trattieni(fotone);
+        }
+    }
+
+    /// Clonazione ottima phase-covariant: la copia trattenuta in memoria ha fedeltà F_E con lo stato spedito,
+    /// quella inoltrata al lettore ha fedeltà 1 - D, con D = (1 - sqrt(1 - 4 (F_E - 1/2)^2)) / 2
+    /// (relazione F_E = 1/2 + sqrt(D (1 - D)) del cloner ottimo, uguale nelle due basi).
+    /// Un errore di fedeltà è modellato come inversione del valore nella polarizzazione del fotone.
+    /// La copia viene misurata nella base corretta dopo l'annuncio, come nella strategia PNS.
+    fn clona_fotone(&mut self, q: &mut QuantumChannel) {
+        let mut rng = generatore();
+        let fedelta = fedelta_clonazione();
+        let disturbo = (1.0 - (1.0 - 4.0 * (fedelta - 0.5).powi(2)).max(0.0).sqrt()) / 2.0;
+
+        let mut copia = q.clone();
+        if rng.gen_bool(1.0 - fedelta) {
+            copia.canale_quantistico.1 ^= 1;
+        }
+        copia.numero_fotoni = 1;
+        if rng.gen_bool(disturbo) {
+            q.canale_quantistico.1 ^= 1;
+        }
+        self.registra(None);
+        self.trattieni(copia);
+    }
+
+    /// Memoria quantistica: il fotone spedito viene trattenuto intatto e al lettore prosegue un fotone
+    /// sostitutivo con polarizzazione e valore casuali. Il fotone trattenuto è misurato nella base corretta
+    /// dopo l'annuncio (conoscenza completa), ma il sostituto non è correlato con lo stato spedito:
+    /// sui bit selezionati il lettore sbaglia nel 50% dei casi, il doppio dell'intercetta e rispedisci.
+    fn memorizza_e_sostituisci(&mut self, q: &mut QuantumChannel) {
+        let mut rng = generatore();
+        let fotone = q.clone();
+        q.spedizione_fotone(base_casuale(), rng.gen_range(0..=1));
+        self.registra(None);
+        self.trattieni(fotone);
+    }
+
+    /// Accecamento (faked-state): misura in una base casuale come nell'intercetta e rispedisci, poi acceca
+    /// i rivelatori del lettore e invia un impulso di innesco con il risultato ottenuto. Il lettore ottiene un
+    /// click solo se misura nella stessa base dell'avversario, e allora con il suo stesso valore: nessun errore
+    /// sui bit selezionati e conoscenza completa della chiave, al prezzo di dimezzare le rilevazioni.
+    /// Senza il modello di rivelatore del lettore (`ATTIVA_RIVELATORE=false`) non c'è nulla da accecare: l'impulso
+    /// di innesco è misurato come un fotone e l'attacco equivale all'intercetta e rispedisci (avviso all'avvio).
+    fn accecamento_stato_forzato(&mut self, q: &mut QuantumChannel) {
+        let pol = base_casuale();
+        let valore = match &mut self.rivelatore {
+            Some(rivelatore) => rivelatore.misura(q, pol),
+            None => Some(q.lettura_fotone(pol)),
+        };
+        match valore {
+            Some(valore) => {
+                q.spedizione_stato_forzato(pol, valore);
+                self.registra(Some((pol, valore)));
+            }
+            None => {
+                q.numero_fotoni = 0;
+                self.registra(None);
+            }
+        }
+    }
+
+    /// Misura dei fotoni in memoria dopo che lo scrittore ha pubblicato le polarizzazioni.
+    /// L'avversario ascolta il canale pubblico senza consumare la pubblicazione destinata al lettore;
+    /// misurando nella base corretta ottiene il valore esatto.
+    pub fn misura_memoria_dopo_annuncio(&mut self, p: &PublicChannel) {
+        for (i, mut fotone) in std::mem::take(&mut self.memoria_quantistica) {
+            let pol = base_da_bit(p.canale_pubblico[i]);
+            let valore = fotone.lettura_fotone(pol);
+            self.avversario_messaggio_quantistico_ricevuto.imposta(i, (pol, valore));
+            self.slot_misurati.imposta(i, true);
+        }
+    }
+
+    /// SARG04: misura dei fotoni in memoria dopo l'annuncio delle coppie di stati. Nessuna base è quella corretta:
+    /// l'avversario misura in una base casuale come il lettore e deduce il bit dalla coppia con la stessa regola
+    /// (`stima_chiave_grezza`), con esito conclusivo quando il valore esclude lo stato della coppia in quella base.
+    pub fn misura_memoria_dopo_coppie(&mut self) {
+        for (i, mut fotone) in std::mem::take(&mut self.memoria_quantistica) {
+            let pol = base_casuale();
+            let valore = fotone.lettura_fotone(pol);
+            self.avversario_messaggio_quantistico_ricevuto.imposta(i, (pol, valore));
+            self.slot_misurati.imposta(i, true);
+        }
+    }
+
+    /// Conta quanti bit della chiave finale dello scrittore l'avversario conosce:
+    /// un bit è noto se l'avversario ha misurato nella base dello scrittore ottenendo lo stesso valore.
+    /// Ritorna (bit noti, bit della chiave finale).
+    pub fn bit_noti_chiave_finale(&self, scr: &Writer) -> (usize, usize) {
+        let mut noti = 0;
+        let mut totale = 0;
+        for (i, in_test) in scr.slot_chiave_grezza.uni().zip(scr.selezione_test.iter()) {
+            // i bit usati per il test non entrano nella chiave finale
+            if in_test { continue; }
+            totale += 1;
+            if self.misura(i) == Some(scr.messaggio_quantistico[i]) {
+                noti += 1;
+            }
+        }
+        (noti, totale)
+    }
+
+    /// BBM92 con sorgente compromessa: l'avversario sostituisce la coppia entangled con due fotoni
+    /// identici in uno stato prodotto (polarizzazione e valore casuali) che conosce in anticipo.
+    /// Scrittore e lettore ottengono valori correlati solo se misurano nella base scelta dall'avversario.
+    pub fn prepara_coppia_compromessa(
+        &mut self,
+        s: &mut EntangledSource,
+        q_scrittore: &mut QuantumChannel,
+        q_lettore: &mut QuantumChannel,
+    ) {
+        let mut rng = generatore();
+        let pol = base_casuale();
+        let valore = rng.gen_range(0..=1);
+        q_scrittore.spedizione_fotone(pol, valore);
+        q_lettore.spedizione_fotone(pol, valore);
+        q_scrittore.set_fotone_in();
+        q_lettore.set_fotone_in();
+        s.coppia_entangled = false;
+        s.coppie_emesse += 1;
+        self.registra(Some((pol, valore)));
+    }
+
+    /// Stima dell'avversario per ogni bit della chiave grezza dello scrittore.
+    /// BB84/BBM92: il valore misurato nello slot (corretto se la base coincide, altrimenti casuale);
+    /// SARG04: il bit dedotto dalla coppia di stati pubblicata con la stessa regola del lettore
+    /// (sugli esiti inconclusivi, che il lettore scarta, la base misurata).
+    /// Negli slot senza misura l'avversario tira a indovinare.
+    pub fn stima_chiave_grezza(&self, scr: &Writer, p: &PublicChannel) -> VettoreBit {
+        let mut rng = generatore();
+        scr.slot_chiave_grezza
+            .uni()
+            .map(|i| {
+                let Some((pol, val)) = self.misura(i) else {
+                    return rng.gen_bool(0.5);
+                };
+                if protocollo() != Protocollo::SARG04 {
+                    return val == 1;
+                }
+                let (val_z, val_x) = p.coppie_stati[i];
+                let (val_candidato, pol_altra) = if pol == POL_Z { (val_z, POL_X) } else { (val_x, POL_Z) };
+                // Esito inconclusivo: lo stato della coppia nella base misurata resta il più probabile (2/3)
+                if val != val_candidato { bit_da_base(pol_altra) == 1 } else { bit_da_base(pol) == 1 }
+            })
+            .collect()
+    }
+
+    /// Conoscenza dell'avversario sulla chiave grezza e sulla chiave finale della sessione.
+    /// Il seme della compressione è pubblico: l'avversario applica la stessa matrice di Toeplitz alla sua stima.
+    pub fn conoscenza_chiave(&self, scr: &Writer, lettr: &Reader, p: &PublicChannel) -> ConoscenzaAvversario {
+        let stima = self.stima_chiave_grezza(scr, p);
+        let accordo = |a: &VettoreBit, b: &VettoreBit| a.len().min(b.len()) - a.differenze(b);
+
+        let bit_chiave = scr.selezione_test.negato();
+        let stima_chiave = stima.seleziona(&bit_chiave);
+        let chiave = scr.chiave_grezza.seleziona(&bit_chiave);
+
+        let mut conoscenza = ConoscenzaAvversario {
+            bit_grezzi: scr.chiave_grezza.len(),
+            accordo_grezza_lettore: accordo(&scr.chiave_grezza, &lettr.chiave_grezza),
+            accordo_grezza_avversario: accordo(&scr.chiave_grezza, &stima),
+            bit_chiave: chiave.len(),
+            accordo_chiave_avversario: accordo(&chiave, &stima_chiave),
+            ..Default::default()
+        };
+        if p.chiave_simmetrica_ok {
+            let n_finali = scr.chiave_simmetrica.len();
+            conoscenza.bit_finali = n_finali;
+            conoscenza.amplificazione = !p.seme_amplificazione.is_empty();
+            let stima_finale = if conoscenza.amplificazione {
+                comprimi_chiave(&stima_chiave, n_finali, &p.seme_amplificazione)
+            } else {
+                stima_chiave
+            };
+            conoscenza.accordo_finale_avversario = accordo(&scr.chiave_simmetrica, &stima_finale);
+        }
+        conoscenza
+    }
 }
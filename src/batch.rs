@@ -0,0 +1,350 @@
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use crate::adversary::ConoscenzaAvversario;
+use crate::config::{
+    attiva_archivio_chiavi, attiva_avversario, fissa_seme_principale, imposta_verboso, lung_msg, numero_thread,
+    parametri_simulazione, seme_principale, MotoreBatch,
+};
+use crate::fast_engine::{esegui_sessione_vettoriale, motore_effettivo};
+use crate::key_store::archivia_sessione;
+use crate::link_model::LinkModel;
+use crate::session::{esegui_sessione, Sessione};
+use crate::session_rng::imposta_seme_sessione;
+use sha2::{Digest, Sha256};
+
+// Le sessioni di un batch sono distribuite su `NUMERO_THREAD` thread. La sessione di indice k usa un generatore
+// seminato da seme principale e k, e i conteggi sono somme: a parità di seme il risultato aggregato non dipende
+// dal numero di thread. Lo sweep numera le sessioni di tutti i punti di seguito e, con un file di checkpoint,
+// registra ogni punto completato per riprendere un'esecuzione interrotta dal primo punto mancante.
+// L'intestazione del checkpoint riporta parametri dello sweep, impronta della configurazione e seme principale.
+
+/// Statistiche aggregate di un batch di sessioni
+/// Somma i conteggi di tutte le sessioni: i tassi sono calcolati sul totale degli impulsi spediti.
+#[derive(Debug, Clone, Default)]
+pub struct StatisticheBatch {
+    pub sessioni: usize,
+    pub sessioni_con_chiave: usize,
+    pub impulsi: usize,
+    pub rilevati: usize,
+    pub bit_selezionati: usize,
+    pub bit_test: usize,
+    pub errori_test: usize,
+    pub bit_chiave_finale: usize,
+    pub conoscenza_avversario: ConoscenzaAvversario, // solo con avversario attivo
+}
+
+impl StatisticheBatch {
+    /// Aggiunge al totale i conteggi di una sessione conclusa
+    pub fn aggiungi(&mut self, s: &Sessione) {
+        self.sessioni += 1;
+        self.impulsi += s.scrittore.messaggio_quantistico.len();
+        self.rilevati += s.lettore.rilevazioni.conta_uni();
+        self.bit_selezionati += s.lettore.esito_letture.conta_uni();
+        self.bit_test += s.scrittore.test_avversario.len().min(s.lettore.test_avversario.len());
+        self.errori_test += s.scrittore.test_avversario.differenze(&s.lettore.test_avversario);
+        if s.canale_pubblico.chiave_simmetrica_ok {
+            self.sessioni_con_chiave += 1;
+            self.bit_chiave_finale += s.scrittore.chiave_simmetrica.len();
+        }
+        if attiva_avversario() {
+            let c = s.avversario.conoscenza_chiave(&s.scrittore, &s.lettore, &s.canale_pubblico);
+            self.conoscenza_avversario.aggiungi(&c);
+        }
+    }
+
+    /// Somma i conteggi di un altro aggregato (parziale di un thread)
+    pub fn unisci(&mut self, altra: &StatisticheBatch) {
+        self.sessioni += altra.sessioni;
+        self.sessioni_con_chiave += altra.sessioni_con_chiave;
+        self.impulsi += altra.impulsi;
+        self.rilevati += altra.rilevati;
+        self.bit_selezionati += altra.bit_selezionati;
+        self.bit_test += altra.bit_test;
+        self.errori_test += altra.errori_test;
+        self.bit_chiave_finale += altra.bit_chiave_finale;
+        self.conoscenza_avversario.aggiungi(&altra.conoscenza_avversario);
+    }
+
+    /// Riga del file di checkpoint: i conteggi separati da tabulazioni
+    fn in_riga(&self) -> String {
+        let c = &self.conoscenza_avversario;
+        [
+            self.sessioni,
+            self.sessioni_con_chiave,
+            self.impulsi,
+            self.rilevati,
+            self.bit_selezionati,
+            self.bit_test,
+            self.errori_test,
+            self.bit_chiave_finale,
+            c.bit_grezzi,
+            c.accordo_grezza_lettore,
+            c.accordo_grezza_avversario,
+            c.bit_chiave,
+            c.accordo_chiave_avversario,
+            c.bit_finali,
+            c.accordo_finale_avversario,
+            usize::from(c.amplificazione),
+        ]
+        .map(|x| x.to_string())
+        .join("\t")
+    }
+
+    fn da_riga(riga: &str) -> Option<Self> {
+        let campi = riga.split('\t').map(|s| s.parse::<usize>().ok()).collect::<Option<Vec<usize>>>()?;
+        let [sessioni, sessioni_con_chiave, impulsi, rilevati, bit_selezionati, bit_test, errori_test, bit_chiave_finale, bit_grezzi, accordo_grezza_lettore, accordo_grezza_avversario, bit_chiave, accordo_chiave_avversario, bit_finali, accordo_finale_avversario, amplificazione] =
+            campi[..]
+        else {
+            return None;
+        };
+        Some(Self {
+            sessioni,
+            sessioni_con_chiave,
+            impulsi,
+            rilevati,
+            bit_selezionati,
+            bit_test,
+            errori_test,
+            bit_chiave_finale,
+            conoscenza_avversario: ConoscenzaAvversario {
+                bit_grezzi,
+                accordo_grezza_lettore,
+                accordo_grezza_avversario,
+                bit_chiave,
+                accordo_chiave_avversario,
+                bit_finali,
+                accordo_finale_avversario,
+                amplificazione: amplificazione == 1,
+            },
+        })
+    }
+
+    /// Frazione di impulsi che hanno prodotto una rilevazione
+    pub fn tasso_rilevazione(&self) -> f64 {
+        if self.impulsi == 0 { 0.0 } else { self.rilevati as f64 / self.impulsi as f64 }
+    }
+
+    /// QBER stimato sui bit di test di tutte le sessioni
+    pub fn qber(&self) -> f64 {
+        if self.bit_test == 0 { 0.0 } else { self.errori_test as f64 / self.bit_test as f64 }
+    }
+
+    /// Tasso di chiave: bit della chiave finale per impulso spedito
+    pub fn tasso_chiave(&self) -> f64 {
+        if self.impulsi == 0 { 0.0 } else { self.bit_chiave_finale as f64 / self.impulsi as f64 }
+    }
+
+    /// Stampa tabellare delle statistiche aggregate
+    pub fn stampa(&self) {
+        fn perc(x: usize, tot: usize) -> f64 { if tot == 0 { 0.0 } else { (x as f64) * 100.0 / (tot as f64) } }
+
+        println!("
+=== Statistiche batch ===");
+        println!("Sessioni eseguite                     : {:>9}", self.sessioni);
+        println!("Seme principale                       : {:>9}", seme_principale());
+        println!("Sessioni con chiave definita          : {:>9} ({:>5.1}%)", self.sessioni_con_chiave, perc(self.sessioni_con_chiave, self.sessioni));
+        println!("Impulsi spediti                       : {:>9}", self.impulsi);
+        println!("Impulsi rilevati                      : {:>9} ({:>5.1}%)", self.rilevati, perc(self.rilevati, self.impulsi));
+        println!("Impulsi persi (nessuna rilevazione)   : {:>9} ({:>5.1}%)", self.impulsi - self.rilevati, perc(self.impulsi - self.rilevati, self.impulsi));
+        println!("Bit selezionati (stessa polarizzaz.)  : {:>9} ({:>5.1}%)", self.bit_selezionati, perc(self.bit_selezionati, self.impulsi));
+        println!("Bit di test / errati                  : {:>9} / {} (QBER {:.2}%)", self.bit_test, self.errori_test, self.qber() * 100.0);
+        println!("Bit chiave finale                     : {:>9}", self.bit_chiave_finale);
+        println!("Tasso di chiave (bit/impulso)         : {:>13.6}", self.tasso_chiave());
+        if attiva_avversario() {
+            let c = &self.conoscenza_avversario;
+            println!("Chiave grezza indovinata (avversario) : {:>9} ({:>5.1}%)", c.accordo_grezza_avversario, perc(c.accordo_grezza_avversario, c.bit_grezzi));
+            println!("I(A:B) / I(A:E) per bit               : {:>9.4} / {:.4}", c.informazione_ab(), c.informazione_ae());
+            println!("Vantaggio lettore I(A:B) - I(A:E)     : {:>9.4}", c.informazione_ab() - c.informazione_ae());
+        }
+    }
+}
+
+/// Esegue `sessioni` sessioni sul collegamento dato senza messaggi degli attori e ne aggrega le statistiche
+/// (con l'archivio attivo le chiavi delle sessioni riuscite sono archiviate), con il motore configurato
+pub fn esegui_batch(collegamento: &LinkModel, sessioni: usize) -> StatisticheBatch {
+    esegui_batch_con_motore(collegamento, sessioni, motore_effettivo())
+}
+
+/// Come `esegui_batch`, con il motore indicato (quello vettoriale solo per configurazioni supportate)
+pub fn esegui_batch_con_motore(collegamento: &LinkModel, sessioni: usize, motore: MotoreBatch) -> StatisticheBatch {
+    esegui_sessioni(collegamento, 0, sessioni, motore, "Batch")
+}
+
+/// Esegue in parallelo le sessioni di indice `primo..primo + sessioni` e ne somma le statistiche.
+/// Ogni thread preleva il prossimo indice libero e accumula un parziale; l'archivio delle chiavi
+/// è scritto da un thread alla volta (le chiavi sono archiviate nell'ordine di completamento).
+fn esegui_sessioni(collegamento: &LinkModel, primo: usize, sessioni: usize, motore: MotoreBatch, etichetta: &str) -> StatisticheBatch {
+    imposta_verboso(false);
+    let prossima = AtomicUsize::new(0);
+    let completate = AtomicUsize::new(0);
+    let archivio = Mutex::new(());
+    // I lavoratori svegliano il thread principale quando finiscono, così il tempo misurato non dipende
+    // dall'intervallo di aggiornamento dell'avanzamento
+    let principale = thread::current();
+    let lavora = || {
+        let mut parziale = StatisticheBatch::default();
+        loop {
+            let k = prossima.fetch_add(1, Ordering::Relaxed);
+            if k >= sessioni {
+                principale.unpark();
+                return parziale;
+            }
+            imposta_seme_sessione((primo + k) as u64);
+            let sessione = match motore {
+                MotoreBatch::Oggetti => esegui_sessione(collegamento),
+                MotoreBatch::Vettoriale => esegui_sessione_vettoriale(collegamento),
+            };
+            parziale.aggiungi(&sessione);
+            if attiva_archivio_chiavi() {
+                let _turno = archivio.lock().unwrap_or_else(|e| e.into_inner());
+                if let Err(e) = archivia_sessione(&sessione) {
+                    eprintln!("Archivio chiavi: {}", e);
+                }
+            }
+            completate.fetch_add(1, Ordering::Relaxed);
+        }
+    };
+
+    let parziali: Vec<StatisticheBatch> = thread::scope(|ambito| {
+        let lavoratori: Vec<_> = (0..numero_thread().clamp(1, sessioni.max(1))).map(|_| ambito.spawn(lavora)).collect();
+        while !lavoratori.iter().all(|l| l.is_finished()) {
+            mostra_avanzamento(etichetta, completate.load(Ordering::Relaxed), sessioni);
+            thread::park_timeout(Duration::from_millis(200));
+        }
+        lavoratori.into_iter().map(|l| l.join().expect("thread del batch terminato con errore")).collect()
+    });
+    cancella_avanzamento();
+
+    let mut statistiche = StatisticheBatch::default();
+    for parziale in &parziali {
+        statistiche.unisci(parziale);
+    }
+    statistiche
+}
+
+/// Avanzamento su stderr, riscritto sulla stessa riga (solo se stderr è un terminale)
+fn mostra_avanzamento(etichetta: &str, completate: usize, totale: usize) {
+    if io::stderr().is_terminal() {
+        let percentuale = if totale == 0 { 100.0 } else { completate as f64 * 100.0 / totale as f64 };
+        eprint!("\r{}: {}/{} sessioni ({:.0}%)   ", etichetta, completate, totale, percentuale);
+        let _ = io::stderr().flush();
+    }
+}
+
+fn cancella_avanzamento() {
+    if io::stderr().is_terminal() {
+        eprint!("\r{}\r", " ".repeat(60));
+    }
+}
+
+/// Curva tasso di chiave / distanza: un batch di `sessioni` sessioni per ogni distanza da 0 a `distanza_max` km.
+/// Con un file di checkpoint i punti già registrati non sono rieseguiti e quelli nuovi sono aggiunti al file.
+pub fn esegui_sweep(collegamento: &LinkModel, distanza_max: f64, passo: f64, sessioni: usize, checkpoint: Option<&Path>) -> io::Result<()> {
+    assert!(passo > 0.0);
+    let intestazione = format!("sweep\t{}\t{}\t{}\t{}", distanza_max, passo, sessioni, lung_msg());
+    let mut completati = match checkpoint {
+        Some(percorso) => leggi_checkpoint(percorso, &intestazione, &impronta_configurazione(collegamento))?,
+        None => Vec::new(),
+    };
+    if !completati.is_empty() {
+        eprintln!("Checkpoint: {} punti già completati", completati.len());
+    }
+
+    println!("=== Tasso di chiave in funzione della distanza ({:?}, seme {}) ===", collegamento.tipo, seme_principale());
+    println!(
+        "{:>9} | {:>10} | {:>10} | {:>11} | {:>7} | {:>14}",
+        "Distanza", "Perdite dB", "Eta", "Rilevazione", "QBER", "Bit/impulso"
+    );
+    println!("{}", "-".repeat(76));
+    let punti = (distanza_max / passo + 1e-9).floor() as usize + 1;
+    for punto in 0..punti {
+        let distanza = punto as f64 * passo;
+        let c = collegamento.con_distanza(distanza);
+        let statistiche = match completati.get(punto) {
+            Some(statistiche) => statistiche.clone(),
+            None => {
+                let etichetta = format!("Punto {}/{} ({:.1} km)", punto + 1, punti, distanza);
+                let statistiche = esegui_sessioni(&c, punto * sessioni, sessioni, motore_effettivo(), &etichetta);
+                if let Some(percorso) = checkpoint {
+                    let mut file = fs::OpenOptions::new().append(true).open(percorso)?;
+                    writeln!(file, "{}", statistiche.in_riga())?;
+                }
+                completati.push(statistiche.clone());
+                statistiche
+            }
+        };
+        println!(
+            "{:>9.1} | {:>10.2} | {:>10.3e} | {:>10.3}% | {:>6.2}% | {:>14.6}",
+            distanza,
+            c.perdite_canale_db(),
+            c.trasmittanza(),
+            statistiche.tasso_rilevazione() * 100.0,
+            statistiche.qber() * 100.0,
+            statistiche.tasso_chiave()
+        );
+    }
+    Ok(())
+}
+
+/// Impronta della configurazione effettiva di uno sweep: SHA-256 (primi 64 bit, in esadecimale) di parametri
+/// della simulazione, collegamento a distanza nulla (la distanza è quella del punto) e motore effettivo
+fn impronta_configurazione(collegamento: &LinkModel) -> String {
+    let descrizione = format!(
+        "{}{:?}\nMOTORE_BATCH={:?}\n",
+        parametri_simulazione(),
+        collegamento.con_distanza(0.0),
+        motore_effettivo()
+    );
+    Sha256::digest(descrizione.as_bytes())[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Legge i punti completati dal file di checkpoint, o lo crea se non esiste.
+/// La prima riga riporta parametri dello sweep, impronta della configurazione e seme principale: un checkpoint
+/// di uno sweep o di una configurazione diversi è rifiutato, e il seme registrato diventa quello dell'esecuzione
+/// (se non ne è configurato un altro).
+/// Una riga finale incompleta (interruzione durante la scrittura) è scartata e il file riscritto senza.
+fn leggi_checkpoint(percorso: &Path, intestazione: &str, impronta: &str) -> io::Result<Vec<StatisticheBatch>> {
+    let contenuto = match fs::read_to_string(percorso) {
+        Ok(contenuto) => contenuto,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            fs::write(percorso, format!("{}\t{}\t{}\n", intestazione, impronta, seme_principale()))?;
+            return Ok(Vec::new());
+        }
+        Err(e) => return Err(e),
+    };
+    let non_valido = |messaggio: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", percorso.display(), messaggio));
+
+    let mut righe = contenuto.lines();
+    let prima = righe.next().unwrap_or_default();
+    let (parametri, seme) = prima.rsplit_once('\t').ok_or_else(|| non_valido("intestazione mancante"))?;
+    let (parametri, impronta_registrata) = parametri.rsplit_once('\t').ok_or_else(|| non_valido("intestazione mancante"))?;
+    if parametri != intestazione {
+        return Err(non_valido("checkpoint di uno sweep con parametri diversi"));
+    }
+    if impronta_registrata != impronta {
+        return Err(non_valido(&format!(
+            "checkpoint di una configurazione diversa (impronta {}, attuale {}): ripristinare le variabili d'ambiente dello sweep interrotto",
+            impronta_registrata, impronta
+        )));
+    }
+    let seme: u64 = seme.parse().map_err(|_| non_valido("seme non valido"))?;
+    if !fissa_seme_principale(seme) {
+        return Err(non_valido("seme principale diverso da quello del checkpoint"));
+    }
+
+    let completati: Vec<StatisticheBatch> = righe.map_while(StatisticheBatch::da_riga).collect();
+    let mut riscritto = format!("{}\n", prima);
+    for statistiche in &completati {
+        riscritto.push_str(&statistiche.in_riga());
+        riscritto.push('\n');
+    }
+    if riscritto != contenuto {
+        fs::write(percorso, riscritto)?;
+    }
+    Ok(completati)
+}
@@ -0,0 +1,427 @@
+use std::fmt;
+use std::ops::Index;
+use crate::config::{bit_da_base, POL_X, POL_Z};
+
+// Vettori di bit impacchettati in parole da 64 bit per chiavi, esiti e fotoni.
+// Un bit occupa un bit di memoria invece di un byte (Vec<u8>/Vec<bool>) o di otto ((char, u8)),
+// e setaccio, parità e XOR lavorano su parole intere: servono alle sessioni con 10^7-10^8 fotoni.
+// I bit oltre la lunghezza nell'ultima parola sono sempre a zero, così confronto e conteggi
+// possono operare direttamente sulle parole.
+
+const BIT_PAROLA: usize = 64;
+
+/// Vettore di bit impacchettato
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct VettoreBit {
+    parole: Vec<u64>,
+    lunghezza: usize,
+}
+
+impl VettoreBit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn con_capacita(bit: usize) -> Self {
+        Self { parole: Vec::with_capacity(bit.div_ceil(BIT_PAROLA)), lunghezza: 0 }
+    }
+
+    /// `n` bit a zero
+    pub fn zeri(n: usize) -> Self {
+        Self { parole: vec![0; n.div_ceil(BIT_PAROLA)], lunghezza: n }
+    }
+
+    /// Conversione in un bit per byte (valori 0/1), per le interfacce che lavorano su slice
+    pub fn in_bit(&self) -> Vec<u8> {
+        self.iter().map(u8::from).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lunghezza
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lunghezza == 0
+    }
+
+    pub fn push(&mut self, bit: bool) {
+        if self.lunghezza.is_multiple_of(BIT_PAROLA) {
+            self.parole.push(0);
+        }
+        if bit {
+            self.parole[self.lunghezza / BIT_PAROLA] |= 1 << (self.lunghezza % BIT_PAROLA);
+        }
+        self.lunghezza += 1;
+    }
+
+    pub fn get(&self, indice: usize) -> Option<bool> {
+        (indice < self.lunghezza).then(|| self.parole[indice / BIT_PAROLA] >> (indice % BIT_PAROLA) & 1 == 1)
+    }
+
+    pub fn imposta(&mut self, indice: usize, bit: bool) {
+        assert!(indice < self.lunghezza, "indice {} oltre la lunghezza {}", indice, self.lunghezza);
+        let maschera = 1 << (indice % BIT_PAROLA);
+        if bit {
+            self.parole[indice / BIT_PAROLA] |= maschera;
+        } else {
+            self.parole[indice / BIT_PAROLA] &= !maschera;
+        }
+    }
+
+    pub fn inverti(&mut self, indice: usize) {
+        assert!(indice < self.lunghezza, "indice {} oltre la lunghezza {}", indice, self.lunghezza);
+        self.parole[indice / BIT_PAROLA] ^= 1 << (indice % BIT_PAROLA);
+    }
+
+    pub fn clear(&mut self) {
+        self.parole.clear();
+        self.lunghezza = 0;
+    }
+
+    pub fn truncate(&mut self, lunghezza: usize) {
+        if lunghezza < self.lunghezza {
+            self.lunghezza = lunghezza;
+            self.parole.truncate(lunghezza.div_ceil(BIT_PAROLA));
+            self.azzera_coda();
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.lunghezza).map(|i| self.parole[i / BIT_PAROLA] >> (i % BIT_PAROLA) & 1 == 1)
+    }
+
+    /// Posizioni dei bit a uno, in ordine crescente (le parole nulle sono saltate per intero)
+    pub fn uni(&self) -> impl Iterator<Item = usize> + '_ {
+        self.parole.iter().enumerate().flat_map(|(k, &parola)| {
+            let mut resto = parola;
+            std::iter::from_fn(move || {
+                if resto == 0 {
+                    return None;
+                }
+                let posizione = resto.trailing_zeros() as usize;
+                resto &= resto - 1;
+                Some(k * BIT_PAROLA + posizione)
+            })
+        })
+    }
+
+    /// Numero di bit a uno
+    pub fn conta_uni(&self) -> usize {
+        self.parole.iter().map(|p| p.count_ones() as usize).sum()
+    }
+
+    /// Parità (XOR) dei bit nelle posizioni `inizio..fine`, per parole da 64 bit
+    pub fn parita_intervallo(&self, inizio: usize, fine: usize) -> u8 {
+        let fine = fine.min(self.lunghezza);
+        let mut accumulo = 0u64;
+        let mut posizione = inizio;
+        while posizione < fine {
+            let presi = (fine - posizione).min(BIT_PAROLA);
+            let maschera = if presi == BIT_PAROLA { !0 } else { (1 << presi) - 1 };
+            accumulo ^= self.parola_da(posizione) & maschera;
+            posizione += presi;
+        }
+        (accumulo.count_ones() & 1) as u8
+    }
+
+    /// XOR bit a bit sulla lunghezza comune
+    pub fn xor(&self, altro: &VettoreBit) -> VettoreBit {
+        self.combina(altro, |a, b| a ^ b)
+    }
+
+    /// OR bit a bit sulla lunghezza comune
+    pub fn or(&self, altro: &VettoreBit) -> VettoreBit {
+        self.combina(altro, |a, b| a | b)
+    }
+
+    /// AND bit a bit sulla lunghezza comune
+    pub fn and(&self, altro: &VettoreBit) -> VettoreBit {
+        self.combina(altro, |a, b| a & b)
+    }
+
+    /// Complemento bit a bit
+    pub fn negato(&self) -> VettoreBit {
+        let mut risultato = Self { parole: self.parole.iter().map(|p| !p).collect(), lunghezza: self.lunghezza };
+        risultato.azzera_coda();
+        risultato
+    }
+
+    /// Numero di bit diversi sulla lunghezza comune (distanza di Hamming)
+    pub fn differenze(&self, altro: &VettoreBit) -> usize {
+        self.xor(altro).conta_uni()
+    }
+
+    /// Accoda i bit di un altro vettore (per parole intere se la lunghezza è allineata)
+    pub fn estendi(&mut self, altro: &VettoreBit) {
+        let scostamento = self.lunghezza % BIT_PAROLA;
+        if scostamento == 0 {
+            self.parole.extend_from_slice(&altro.parole);
+        } else {
+            for &parola in &altro.parole {
+                *self.parole.last_mut().expect("vettore non vuoto") |= parola << scostamento;
+                self.parole.push(parola >> (BIT_PAROLA - scostamento));
+            }
+        }
+        self.lunghezza += altro.lunghezza;
+        self.parole.truncate(self.lunghezza.div_ceil(BIT_PAROLA));
+    }
+
+    /// Setaccio: i bit nelle posizioni in cui la maschera vale uno, nello stesso ordine
+    pub fn seleziona(&self, maschera: &VettoreBit) -> VettoreBit {
+        let mut selezionati = VettoreBit::new();
+        for (k, (&parola, &filtro)) in self.parole.iter().zip(maschera.parole.iter()).enumerate() {
+            // Nell'ultima parola solo le posizioni entro la lunghezza di questo vettore
+            let valide = self.lunghezza - k * BIT_PAROLA;
+            let mut resto = if valide >= BIT_PAROLA { filtro } else { filtro & ((1 << valide) - 1) };
+            while resto != 0 {
+                selezionati.push(parola >> resto.trailing_zeros() & 1 == 1);
+                resto &= resto - 1;
+            }
+        }
+        selezionati
+    }
+
+    /// Bit in ordine inverso
+    pub fn rovesciato(&self) -> VettoreBit {
+        (0..self.lunghezza).rev().map(|i| self[i]).collect()
+    }
+
+    /// Parità del prodotto (AND) con la finestra di questo vettore che inizia alla posizione indicata:
+    /// prodotto scalare in GF(2) usato dalle matrici di Toeplitz
+    pub fn parita_finestra(&self, inizio: usize, altro: &VettoreBit) -> u8 {
+        let uni: u32 = altro
+            .parole
+            .iter()
+            .enumerate()
+            .map(|(k, &parola)| (self.parola_da(inizio + k * BIT_PAROLA) & parola).count_ones())
+            .sum();
+        (uni & 1) as u8
+    }
+
+    /// Vettore di `n` bit costruito da parole già pronte (i bit oltre `n` sono scartati)
+    pub fn da_parole(mut parole: Vec<u64>, n: usize) -> Self {
+        parole.resize(n.div_ceil(BIT_PAROLA), 0);
+        let mut vettore = Self { parole, lunghezza: n };
+        vettore.azzera_coda();
+        vettore
+    }
+
+    /// 64 bit a partire da una posizione qualsiasi (zeri oltre la lunghezza)
+    fn parola_da(&self, posizione: usize) -> u64 {
+        let (k, scostamento) = (posizione / BIT_PAROLA, posizione % BIT_PAROLA);
+        let bassa = self.parole.get(k).map_or(0, |p| p >> scostamento);
+        if scostamento == 0 {
+            return bassa;
+        }
+        bassa | self.parole.get(k + 1).map_or(0, |p| p << (BIT_PAROLA - scostamento))
+    }
+
+    fn combina(&self, altro: &VettoreBit, operazione: impl Fn(u64, u64) -> u64) -> VettoreBit {
+        let parole = self.parole.iter().zip(altro.parole.iter()).map(|(&a, &b)| operazione(a, b)).collect();
+        let mut risultato = Self { parole, lunghezza: self.lunghezza.min(altro.lunghezza) };
+        risultato.azzera_coda();
+        risultato
+    }
+
+    fn azzera_coda(&mut self) {
+        let resto = self.lunghezza % BIT_PAROLA;
+        if resto != 0 {
+            if let Some(ultima) = self.parole.last_mut() {
+                *ultima &= (1 << resto) - 1;
+            }
+        }
+    }
+}
+
+impl Index<usize> for VettoreBit {
+    type Output = bool;
+
+    fn index(&self, indice: usize) -> &bool {
+        match self.get(indice) {
+            Some(true) => &true,
+            Some(false) => &false,
+            None => panic!("indice {} oltre la lunghezza {}", indice, self.lunghezza),
+        }
+    }
+}
+
+impl FromIterator<bool> for VettoreBit {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut vettore = VettoreBit::new();
+        vettore.extend(iter);
+        vettore
+    }
+}
+
+impl Extend<bool> for VettoreBit {
+    fn extend<I: IntoIterator<Item = bool>>(&mut self, iter: I) {
+        for bit in iter {
+            self.push(bit);
+        }
+    }
+}
+
+/// Stessa stampa di un vettore di bit 0/1
+impl fmt::Debug for VettoreBit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter().map(u8::from)).finish()
+    }
+}
+
+/// Sequenza di fotoni (polarizzazione, valore) impacchettata in due vettori di bit:
+/// base (Z -> 0, X -> 1) e valore
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SequenzaFotoni {
+    pub basi: VettoreBit,
+    pub valori: VettoreBit,
+}
+
+/// Fotoni indicizzati da base e valore (2 * base + valore), per restituirli per riferimento
+const FOTONI: [(char, u8); 4] = [(POL_Z, 0), (POL_Z, 1), (POL_X, 0), (POL_X, 1)];
+
+impl SequenzaFotoni {
+    pub fn con_capacita(n: usize) -> Self {
+        Self { basi: VettoreBit::con_capacita(n), valori: VettoreBit::con_capacita(n) }
+    }
+
+    pub fn push(&mut self, (polarizzazione, valore): (char, u8)) {
+        debug_assert!(polarizzazione == POL_Z || polarizzazione == POL_X);
+        self.basi.push(bit_da_base(polarizzazione) == 1);
+        self.valori.push(valore == 1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.basi.len()
+    }
+
+    pub fn imposta(&mut self, indice: usize, (polarizzazione, valore): (char, u8)) {
+        debug_assert!(polarizzazione == POL_Z || polarizzazione == POL_X);
+        self.basi.imposta(indice, bit_da_base(polarizzazione) == 1);
+        self.valori.imposta(indice, valore == 1);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (char, u8)> + '_ {
+        (0..self.len()).map(|i| self[i])
+    }
+
+    pub fn estendi(&mut self, altra: &SequenzaFotoni) {
+        self.basi.estendi(&altra.basi);
+        self.valori.estendi(&altra.valori);
+    }
+}
+
+impl Index<usize> for SequenzaFotoni {
+    type Output = (char, u8);
+
+    fn index(&self, indice: usize) -> &(char, u8) {
+        &FOTONI[2 * usize::from(self.basi[indice]) + usize::from(self.valori[indice])]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Lunghezze attorno ai confini di parola
+    const LUNGHEZZE: [usize; 9] = [0, 1, 63, 64, 65, 127, 128, 129, 1_000];
+
+    fn casuali(rng: &mut StdRng, n: usize) -> Vec<bool> {
+        (0..n).map(|_| rng.gen_bool(0.5)).collect()
+    }
+
+    /// I bit oltre la lunghezza nell'ultima parola sono a zero e non ci sono parole in eccesso
+    fn coda_azzerata(v: &VettoreBit) -> bool {
+        let resto = v.lunghezza % BIT_PAROLA;
+        v.parole.len() == v.lunghezza.div_ceil(BIT_PAROLA)
+            && (resto == 0 || v.parole.last().is_some_and(|&p| p >> resto == 0))
+    }
+
+    #[test]
+    fn indicizzazione_per_parole() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for n in LUNGHEZZE {
+            let bit = casuali(&mut rng, n);
+            let mut v: VettoreBit = bit.iter().copied().collect();
+            assert_eq!(v.len(), n);
+            assert!(v.iter().eq(bit.iter().copied()), "n = {}", n);
+            for (i, &b) in bit.iter().enumerate() {
+                assert_eq!(v.get(i), Some(b));
+                assert_eq!(v[i], b);
+            }
+            assert_eq!(v.get(n), None);
+
+            // Scrittura ai confini di parola senza toccare i bit vicini
+            for i in [0, 63, 64, 127, 128].into_iter().filter(|&i| i < n) {
+                v.imposta(i, true);
+                assert!(v[i]);
+                v.inverti(i);
+                assert!(!v[i]);
+                v.imposta(i, bit[i]);
+            }
+            assert!(v.iter().eq(bit.iter().copied()), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn coda_azzerata_dopo_le_operazioni() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for n in LUNGHEZZE {
+            let a: VettoreBit = casuali(&mut rng, n).into_iter().collect();
+            let b: VettoreBit = casuali(&mut rng, n + 3).into_iter().collect();
+            assert!(coda_azzerata(&a.negato()), "negato, n = {}", n);
+            assert!(coda_azzerata(&a.xor(&b)) && coda_azzerata(&a.or(&b)), "n = {}", n);
+            assert!(coda_azzerata(&VettoreBit::da_parole(vec![!0; 20], n)), "da_parole, n = {}", n);
+            assert_eq!(VettoreBit::da_parole(vec![!0; 20], n).conta_uni(), n);
+
+            let mut troncato = a.negato();
+            troncato.truncate(n / 2);
+            assert!(coda_azzerata(&troncato), "truncate, n = {}", n);
+            assert_eq!(troncato, a.negato().iter().take(n / 2).collect::<VettoreBit>());
+
+            // Accodamento non allineato: stessi bit della concatenazione
+            let mut esteso = b.clone();
+            esteso.estendi(&a.negato());
+            assert!(coda_azzerata(&esteso), "estendi, n = {}", n);
+            assert!(esteso.iter().eq(b.iter().chain(a.negato().iter())));
+        }
+    }
+
+    #[test]
+    fn conteggi_e_posizioni() {
+        let mut rng = StdRng::seed_from_u64(13);
+        for n in LUNGHEZZE {
+            let bit_a = casuali(&mut rng, n);
+            let bit_b = casuali(&mut rng, n);
+            let a: VettoreBit = bit_a.iter().copied().collect();
+            let b: VettoreBit = bit_b.iter().copied().collect();
+
+            assert_eq!(a.conta_uni(), bit_a.iter().filter(|&&x| x).count());
+            assert_eq!(a.negato().conta_uni(), n - a.conta_uni());
+            assert!(a.uni().eq(bit_a.iter().enumerate().filter(|(_, &x)| x).map(|(i, _)| i)));
+            assert_eq!(a.differenze(&b), bit_a.iter().zip(&bit_b).filter(|(x, y)| x != y).count());
+
+            let selezionati: Vec<bool> = bit_a.iter().zip(&bit_b).filter(|(_, &m)| m).map(|(&x, _)| x).collect();
+            assert!(a.seleziona(&b).iter().eq(selezionati));
+
+            let (inizio, fine) = (n / 3, n - n / 5);
+            let parita = bit_a[inizio..fine].iter().filter(|&&x| x).count() % 2;
+            assert_eq!(usize::from(a.parita_intervallo(inizio, fine)), parita, "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn sequenza_fotoni_per_slot() {
+        let mut sequenza = SequenzaFotoni::con_capacita(4);
+        for fotone in FOTONI {
+            sequenza.push(fotone);
+        }
+        assert!(sequenza.iter().eq(FOTONI));
+        sequenza.imposta(0, (POL_X, 1));
+        sequenza.imposta(3, (POL_Z, 0));
+        assert_eq!(sequenza[0], (POL_X, 1));
+        assert_eq!(sequenza[3], (POL_Z, 0));
+        assert_eq!(sequenza[1], (POL_Z, 1));
+    }
+}
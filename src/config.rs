@@ -1,12 +1,177 @@
 // Configurazione globale del progetto BB84 (senza concorrenza)
 // Le costanti devono essere note a scrittore, lettore e avversario.
 
+use rand::Rng;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use crate::session_rng::generatore;
+
 pub const LUNG_MSG: usize = 64;         // lunghezza del messaggio (numero di fotoni) è un parametro che può essere modificato
 pub const ATTIVA_AVVERSARIO: bool = false; // default: avversario disattivato; impostare a true per attivarlo
+pub const STRATEGIA_AVVERSARIO: StrategiaAvversario = StrategiaAvversario::IntercettaRispedisci;
+pub const PROTOCOLLO: Protocollo = Protocollo::BB84; // default: BB84 "prepara e misura"
+pub const FRAZIONE_ATTACCO: f64 = 1.0; // frazione degli impulsi attaccati; gli altri proseguono indisturbati
+pub const FOTONI_SONDA: f64 = 1e4;               // cavallo di Troia: fotoni medi iniettati nel modulatore per impulso
+pub const RIFLESSIONE_DISPOSITIVO_DB: f64 = 30.0; // perdita della luce riflessa dal modulatore verso l'avversario
+pub const ATTENUAZIONE_ISOLATORE_DB: f64 = 0.0;   // isolatore ottico dello scrittore (per passaggio; 0 = assente)
+pub const ATTIVA_MONITOR_SCRITTORE: bool = false; // rivelatore di monitor della luce in ingresso allo scrittore
+pub const FRAZIONE_MONITOR: f64 = 0.01;           // frazione della luce in ingresso prelevata dal monitor
+pub const FEDELTA_CLONAZIONE: f64 = 0.8536; // fedeltà della copia dell'avversario (0.8536: cloner simmetrico, QBER ~14.6%)
+
+// Sorgente laser attenuata (impulsi coerenti deboli)
+pub const ATTIVA_SORGENTE_LASER: bool = false; // default: sorgente ideale a singolo fotone
+pub const MU: f64 = 0.5;                       // numero medio di fotoni per impulso (distribuzione di Poisson)
+
+// Decoy-state (solo BB84, richiede la sorgente laser che viene attivata automaticamente)
+pub const ATTIVA_DECOY: bool = false;     // default: tutti gli impulsi hanno intensità MU
+pub const MU_ESCA: f64 = 0.1;             // intensità media degli impulsi esca (ν < μ)
+pub const PROB_SEGNALE: f64 = 0.8;        // probabilità di un impulso segnale (intensità MU)
+pub const PROB_ESCA: f64 = 0.1;           // probabilità di un impulso esca; il resto sono impulsi vuoti
+pub const FATTORE_RICONCILIAZIONE: f64 = 1.16; // f: inefficienza della correzione errori (leak = f·h(E))
+
+// Analisi a chiave finita (default: disattivata, la chiave finale è la chiave grezza senza i bit di test)
+pub const ATTIVA_CHIAVE_FINITA: bool = false;
+pub const EPSILON_SEC: f64 = 1e-10; // probabilità di fallimento ammessa per la segretezza
+pub const EPSILON_COR: f64 = 1e-12; // probabilità di fallimento ammessa per la correttezza
+
+// Riconciliazione degli errori (default: nessuna, qualsiasi errore nei bit di test interrompe il processo)
+pub const RICONCILIAZIONE: TipoRiconciliazione = TipoRiconciliazione::Nessuna;
+pub const SOGLIA_QBER: f64 = 0.11; // con riconciliazione: QBER di test oltre il quale il processo viene interrotto
+
+// Collegamento scrittore -> lettore (default: nessuna perdita, comportamento ideale)
+pub const TIPO_COLLEGAMENTO: TipoCollegamento = TipoCollegamento::Fibra;
+pub const DISTANZA_KM: f64 = 0.0;
+pub const ATTENUAZIONE_FIBRA_DB_KM: f64 = 0.2;          // fibra monomodale a 1550 nm
+pub const ATTENUAZIONE_SPAZIO_LIBERO_DB_KM: f64 = 0.1;  // assorbimento atmosferico in aria limpida
+pub const PERDITE_INSERZIONE_DB: f64 = 0.0;             // connettori, ottiche del ricevitore, ecc.
+pub const EFFICIENZA_RIVELATORE: f64 = 1.0;
+pub const DIAMETRO_TRASMETTITORE_M: f64 = 0.1;          // spazio libero: apertura del telescopio trasmittente
+pub const DIAMETRO_RICEVITORE_M: f64 = 0.5;             // spazio libero: apertura del telescopio ricevente
+pub const DIVERGENZA_URAD: f64 = 10.0;                  // spazio libero: divergenza del fascio (µrad)
+
+// Modello dei rivelatori a singolo fotone (default: lettura ideale, sempre un risultato)
+pub const ATTIVA_RIVELATORE: bool = false;             // modello di rivelatore per il lettore
+pub const ATTIVA_RIVELATORE_AVVERSARIO: bool = false;  // stesso modello anche per l'avversario
+pub const PROB_CONTEGGIO_BUIO: f64 = 1e-5;             // probabilità di conteggio di buio per rivelatore e slot
+pub const TEMPO_MORTO_SLOT: u32 = 0;                   // slot ciechi dopo ogni click
+pub const PROB_AFTERPULSE: f64 = 0.01;                 // probabilità di afterpulse nello slot attivo successivo a un click
+// Contromisure all'accecamento dei rivelatori (default: disattivate)
+pub const ATTIVA_FOTODIODO_SORVEGLIANZA: bool = false;  // fotodiodo che rileva luce intensa in ingresso al lettore
+pub const PROB_VERIFICA_EFFICIENZA: f64 = 0.0;          // frazione di slot con efficienza ridotta a caso (monitoraggio)
+pub const FATTORE_VERIFICA_EFFICIENZA: f64 = 0.5;       // riduzione dell'efficienza negli slot di verifica
+
+// Archivio delle chiavi generate (default: disattivato, la chiave finale non sopravvive al processo)
+pub const ATTIVA_ARCHIVIO_CHIAVI: bool = false;
+pub const DIR_ARCHIVIO_CHIAVI: &str = "archivio_chiavi"; // un file per coppia di nodi e per lato
+pub const NODO_SCRITTORE: &str = "scrittore";            // identificativo del nodo dello scrittore
+pub const NODO_LETTORE: &str = "lettore";                // identificativo del nodo del lettore
+pub const PORTA_KME: u16 = 8014;                         // porta del server di consegna chiavi (ETSI GS QKD 014) su localhost
+
+// Motore delle esecuzioni batch (default: attori e canali simulati fotone per fotone)
+pub const MOTORE_BATCH: MotoreBatch = MotoreBatch::Oggetti;
+pub const NUMERO_THREAD: usize = 0;            // thread delle esecuzioni batch e sweep (0: tutti i core disponibili)
+pub const SEME_PRINCIPALE: Option<u64> = None; // seme da cui derivano quelli delle sessioni (None: estratto a caso)
+
+// Esecuzione: messaggi dei singoli attori a terminale (disattivati nelle esecuzioni batch)
+static VERBOSO: AtomicBool = AtomicBool::new(true);
+// Seme principale effettivo, fissato al primo uso
+static SEME: OnceLock<u64> = OnceLock::new();
 
 // Polarizzazioni ammesse
 pub const POL_Z: char = 'Z';
 pub const POL_X: char = 'X';
+pub const PROB_BASE_Z: f64 = 0.5; // probabilità di scegliere la base Z (0.5: BB84 simmetrico; es. 0.9: "efficient BB84")
+
+/// Protocolli simulabili
+/// - `BB84`: lo scrittore prepara i fotoni e li spedisce al lettore
+/// - `BBM92`: una sorgente esterna (non fidata) distribuisce coppie entangled, scrittore e lettore misurano entrambi
+/// - `SARG04`: fotoni come in BB84, ma lo scrittore annuncia una coppia di stati non ortogonali invece della base
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocollo {
+	BB84,
+	BBM92,
+	SARG04,
+}
+
+/// Schema di riconciliazione degli errori tra le chiavi di scrittore e lettore
+/// - `Nessuna`: nessuna correzione, un solo errore nei bit di test interrompe il processo
+/// - `Winnow`: parità dei blocchi e sindromi di Hamming su blocchi crescenti
+/// - `Ldpc`: sindrome di un codice LDPC decodificata con belief propagation, a tasso adattivo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipoRiconciliazione {
+	Nessuna,
+	Winnow,
+	Ldpc,
+}
+
+/// Motore di simulazione delle sessioni di batch e sweep
+/// - `Oggetti`: scrittore, avversario e lettore si scambiano un fotone alla volta sul canale quantistico
+/// - `Vettoriale`: basi, valori, attacchi e rilevazioni estratti in blocco per parole da 64 bit
+///   (solo per le configurazioni supportate, altrimenti si usa `Oggetti`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotoreBatch {
+	Oggetti,
+	Vettoriale,
+}
+
+/// Tipo di collegamento quantistico tra scrittore e lettore
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipoCollegamento {
+	Fibra,
+	SpazioLibero,
+}
+
+/// Strategie di attacco dell'avversario (usate solo se ATTIVA_AVVERSARIO=true)
+/// - `IntercettaRispedisci`: misura ogni fotone in una base casuale prima del lettore
+/// - `Pns`: photon-number-splitting; blocca gli impulsi a singolo fotone, trattiene un fotone
+///   dagli impulsi multi-fotone in una memoria quantistica e lo misura dopo l'annuncio delle basi
+/// - `Clonazione`: clonazione ottima phase-covariant; trattiene una copia imperfetta di ogni fotone
+///   (fedeltà `FEDELTA_CLONAZIONE`) e inoltra l'altra al lettore, disturbata di conseguenza
+/// - `Memoria`: trattiene il fotone in una memoria quantistica, inoltra al lettore un fotone sostitutivo
+///   preparato a caso e misura il fotone trattenuto dopo l'annuncio delle basi (conoscenza completa, QBER 50%)
+/// - `Accecamento`: acceca i rivelatori del lettore con luce intensa e forza click pari alla propria misura
+///   (stati forzati); richiede il modello di rivelatore del lettore
+/// - `CavalloDiTroia`: sonda il modulatore dello scrittore con luce iniettata per leggerne la base e
+///   misura senza disturbo i soli impulsi di cui ha letto la base
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrategiaAvversario {
+	IntercettaRispedisci,
+	Pns,
+	Clonazione,
+	Memoria,
+	Accecamento,
+	CavalloDiTroia,
+}
+
+/// Probabilità di scegliere la base Z (variabile `PROB_BASE_Z`, strettamente tra 0 e 1)
+pub fn prob_base_z() -> f64 {
+	leggi_f64_env("PROB_BASE_Z", PROB_BASE_Z, |p| p > 0.0 && p < 1.0)
+}
+
+/// Sceglie una base casuale secondo `PROB_BASE_Z` (stessa distribuzione per scrittore, lettore e avversario)
+pub fn base_casuale() -> char {
+	if generatore().gen_bool(prob_base_z()) { POL_Z } else { POL_X }
+}
+
+/// `true` se le due basi sono equiprobabili (BB84 standard)
+pub fn basi_simmetriche() -> bool {
+	prob_base_z() == 0.5
+}
+
+/// Base scelta più spesso (usata per la chiave); l'altra è la base minoritaria (usata per il test)
+pub fn base_maggioritaria() -> char {
+	if prob_base_z() >= 0.5 { POL_Z } else { POL_X }
+}
+
+/// SARG04: il bit della chiave è codificato nella base dello stato (Z -> 0, X -> 1)
+pub fn bit_da_base(polarizzazione: char) -> u8 {
+	if polarizzazione == POL_Z { 0 } else { 1 }
+}
+
+/// Base codificata da un bit dei vettori impacchettati (0 -> Z, 1 -> X)
+pub fn base_da_bit(bit: bool) -> char {
+	if bit { POL_X } else { POL_Z }
+}
 
 /// Legge `LUNG_MSG` da environment variabile `LUNG_MSG`, altrimenti ritorna il valore di default.
 pub fn lung_msg() -> usize {
@@ -15,14 +180,357 @@ pub fn lung_msg() -> usize {
 		.unwrap_or(LUNG_MSG)
 }
 
+/// Thread delle esecuzioni batch e sweep (variabile `NUMERO_THREAD`; 0 o assente: tutti i core disponibili)
+pub fn numero_thread() -> usize {
+	let thread = std::env::var("NUMERO_THREAD").ok()
+		.and_then(|s| s.parse::<usize>().ok())
+		.unwrap_or(NUMERO_THREAD);
+	match thread {
+		0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+		n => n,
+	}
+}
+
+/// Seme principale delle sessioni (variabile `SEME`): se non è configurato è estratto a caso al primo uso
+/// e resta lo stesso per tutto il processo, così da poter ripetere l'esecuzione
+pub fn seme_principale() -> u64 {
+	*SEME.get_or_init(|| seme_configurato().unwrap_or_else(|| rand::thread_rng().gen()))
+}
+
+/// Fissa il seme principale (ripresa di uno sweep); ritorna `false` se è già fissato, o configurato, con un altro valore
+pub fn fissa_seme_principale(seme: u64) -> bool {
+	*SEME.get_or_init(|| seme_configurato().unwrap_or(seme)) == seme
+}
+
+fn seme_configurato() -> Option<u64> {
+	std::env::var("SEME").ok()
+		.and_then(|s| s.parse::<u64>().ok())
+		.or(SEME_PRINCIPALE)
+}
+
+/// Valori effettivi dei parametri che influenzano l'esito delle sessioni (protocollo, sorgente, avversario,
+/// rivelatori, post-elaborazione), uno per riga come `NOME=valore`. Restano fuori seme, thread, archivio
+/// e collegamento, che è descritto dal proprio `LinkModel`.
+pub fn parametri_simulazione() -> String {
+	let parametri: [(&str, String); 29] = [
+		("PROTOCOLLO", format!("{:?}", protocollo())),
+		("LUNG_MSG", lung_msg().to_string()),
+		("PROB_BASE_Z", prob_base_z().to_string()),
+		("ATTIVA_SORGENTE_LASER", attiva_sorgente_laser().to_string()),
+		("MU", mu().to_string()),
+		("ATTIVA_DECOY", attiva_decoy().to_string()),
+		("MU_ESCA", mu_esca().to_string()),
+		("PROB_SEGNALE", prob_segnale().to_string()),
+		("PROB_ESCA", prob_esca().to_string()),
+		("ATTIVA_AVVERSARIO", attiva_avversario().to_string()),
+		("STRATEGIA_AVVERSARIO", format!("{:?}", strategia_avversario())),
+		("FRAZIONE_ATTACCO", frazione_attacco().to_string()),
+		("FEDELTA_CLONAZIONE", fedelta_clonazione().to_string()),
+		("FOTONI_SONDA", fotoni_sonda().to_string()),
+		("ATTENUAZIONE_ISOLATORE_DB", attenuazione_isolatore_db().to_string()),
+		("ATTIVA_MONITOR_SCRITTORE", attiva_monitor_scrittore().to_string()),
+		("ATTIVA_RIVELATORE", attiva_rivelatore().to_string()),
+		("ATTIVA_RIVELATORE_AVVERSARIO", attiva_rivelatore_avversario().to_string()),
+		("PROB_CONTEGGIO_BUIO", prob_conteggio_buio().to_string()),
+		("TEMPO_MORTO_SLOT", tempo_morto_slot().to_string()),
+		("PROB_AFTERPULSE", prob_afterpulse().to_string()),
+		("ATTIVA_FOTODIODO_SORVEGLIANZA", attiva_fotodiodo_sorveglianza().to_string()),
+		("PROB_VERIFICA_EFFICIENZA", prob_verifica_efficienza().to_string()),
+		("RICONCILIAZIONE", format!("{:?}", riconciliazione())),
+		("FATTORE_RICONCILIAZIONE", fattore_riconciliazione().to_string()),
+		("SOGLIA_QBER", soglia_qber().to_string()),
+		("ATTIVA_CHIAVE_FINITA", attiva_chiave_finita().to_string()),
+		("EPSILON_SEC", epsilon_sec().to_string()),
+		("EPSILON_COR", epsilon_cor().to_string()),
+	];
+	parametri.iter().map(|(nome, valore)| format!("{}={}\n", nome, valore)).collect()
+}
+
 /// Restituisce `true` se la variabile d'ambiente `ATTIVA_AVVERSARIO` è impostata
 /// su uno dei valori `1`, `true` (case-insensitive). Altrimenti ritorna il default.
 pub fn attiva_avversario() -> bool {
-	std::env::var("ATTIVA_AVVERSARIO").ok()
+	leggi_bool_env("ATTIVA_AVVERSARIO", ATTIVA_AVVERSARIO)
+}
+
+/// Restituisce `true` se è attiva la sorgente laser attenuata (variabile `ATTIVA_SORGENTE_LASER`).
+pub fn attiva_sorgente_laser() -> bool {
+	leggi_bool_env("ATTIVA_SORGENTE_LASER", ATTIVA_SORGENTE_LASER)
+}
+
+/// Frazione degli impulsi su cui l'avversario agisce (variabile `FRAZIONE_ATTACCO`, tra 0 e 1)
+pub fn frazione_attacco() -> f64 {
+	leggi_f64_env("FRAZIONE_ATTACCO", FRAZIONE_ATTACCO, |f| (0.0..=1.0).contains(&f))
+}
+
+/// Fotoni medi della sonda del cavallo di Troia (variabile `FOTONI_SONDA`, >= 0)
+pub fn fotoni_sonda() -> f64 {
+	leggi_f64_env("FOTONI_SONDA", FOTONI_SONDA, |n| n >= 0.0)
+}
+
+/// Attenuazione dell'isolatore ottico dello scrittore per passaggio (variabile `ATTENUAZIONE_ISOLATORE_DB`, >= 0)
+pub fn attenuazione_isolatore_db() -> f64 {
+	leggi_f64_env("ATTENUAZIONE_ISOLATORE_DB", ATTENUAZIONE_ISOLATORE_DB, |a| a >= 0.0)
+}
+
+/// Restituisce `true` se lo scrittore monitora la luce in ingresso (variabile `ATTIVA_MONITOR_SCRITTORE`)
+pub fn attiva_monitor_scrittore() -> bool {
+	leggi_bool_env("ATTIVA_MONITOR_SCRITTORE", ATTIVA_MONITOR_SCRITTORE)
+}
+
+/// Fedeltà della copia trattenuta dall'avversario nella strategia di clonazione
+/// (variabile `FEDELTA_CLONAZIONE`, tra 0.5 e 1)
+pub fn fedelta_clonazione() -> f64 {
+	leggi_f64_env("FEDELTA_CLONAZIONE", FEDELTA_CLONAZIONE, |f| (0.5..=1.0).contains(&f))
+}
+
+/// Legge il numero medio di fotoni per impulso μ dalla variabile `MU` (deve essere >= 0).
+pub fn mu() -> f64 {
+	leggi_f64_env("MU", MU, |m| m >= 0.0)
+}
+
+/// Legge la strategia dell'avversario dalla variabile `STRATEGIA_AVVERSARIO`
+/// (`intercetta_rispedisci`, `pns`, `clonazione`, `memoria`, `accecamento`, `cavallo_di_troia`, case-insensitive). Valori non riconosciuti ritornano il default.
+pub fn strategia_avversario() -> StrategiaAvversario {
+	std::env::var("STRATEGIA_AVVERSARIO").ok()
+		.and_then(|s| match s.to_lowercase().as_str() {
+			"intercetta_rispedisci" => Some(StrategiaAvversario::IntercettaRispedisci),
+			"pns" => Some(StrategiaAvversario::Pns),
+			"clonazione" => Some(StrategiaAvversario::Clonazione),
+			"memoria" => Some(StrategiaAvversario::Memoria),
+			"accecamento" => Some(StrategiaAvversario::Accecamento),
+			"cavallo_di_troia" => Some(StrategiaAvversario::CavalloDiTroia),
+			_ => None,
+		})
+		.unwrap_or(STRATEGIA_AVVERSARIO)
+}
+
+/// Restituisce `true` se è attivo il protocollo decoy-state (variabile `ATTIVA_DECOY`).
+/// Il decoy-state è previsto solo per BB84: con altri protocolli ritorna sempre `false`.
+pub fn attiva_decoy() -> bool {
+	leggi_bool_env("ATTIVA_DECOY", ATTIVA_DECOY) && protocollo() == Protocollo::BB84
+}
+
+/// Intensità degli impulsi esca ν (variabile `MU_ESCA`, deve essere > 0)
+pub fn mu_esca() -> f64 {
+	leggi_f64_env("MU_ESCA", MU_ESCA, |v| v > 0.0)
+}
+
+/// Probabilità di un impulso segnale (variabile `PROB_SEGNALE`, tra 0 e 1)
+pub fn prob_segnale() -> f64 {
+	leggi_f64_env("PROB_SEGNALE", PROB_SEGNALE, |p| (0.0..=1.0).contains(&p))
+}
+
+/// Probabilità di un impulso esca (variabile `PROB_ESCA`, tra 0 e 1)
+pub fn prob_esca() -> f64 {
+	leggi_f64_env("PROB_ESCA", PROB_ESCA, |p| (0.0..=1.0).contains(&p))
+}
+
+/// Inefficienza della correzione errori f (variabile `FATTORE_RICONCILIAZIONE`, >= 1)
+pub fn fattore_riconciliazione() -> f64 {
+	leggi_f64_env("FATTORE_RICONCILIAZIONE", FATTORE_RICONCILIAZIONE, |f| f >= 1.0)
+}
+
+/// Restituisce `true` se la chiave finale è dimensionata con l'analisi a chiave finita (variabile `ATTIVA_CHIAVE_FINITA`).
+/// Con il decoy-state la lunghezza è già fissata dalla stima decoy: in quel caso ritorna `false`.
+pub fn attiva_chiave_finita() -> bool {
+	leggi_bool_env("ATTIVA_CHIAVE_FINITA", ATTIVA_CHIAVE_FINITA) && !attiva_decoy()
+}
+
+/// Parametro di segretezza ε_sec (variabile `EPSILON_SEC`, strettamente tra 0 e 1)
+pub fn epsilon_sec() -> f64 {
+	leggi_f64_env("EPSILON_SEC", EPSILON_SEC, |e| e > 0.0 && e < 1.0)
+}
+
+/// Parametro di correttezza ε_cor (variabile `EPSILON_COR`, strettamente tra 0 e 1)
+pub fn epsilon_cor() -> f64 {
+	leggi_f64_env("EPSILON_COR", EPSILON_COR, |e| e > 0.0 && e < 1.0)
+}
+
+/// Schema di riconciliazione dalla variabile `RICONCILIAZIONE` (`nessuna`, `winnow`, `ldpc`)
+pub fn riconciliazione() -> TipoRiconciliazione {
+	std::env::var("RICONCILIAZIONE").ok()
+		.and_then(|s| match s.to_lowercase().as_str() {
+			"nessuna" => Some(TipoRiconciliazione::Nessuna),
+			"winnow" => Some(TipoRiconciliazione::Winnow),
+			"ldpc" => Some(TipoRiconciliazione::Ldpc),
+			_ => None,
+		})
+		.unwrap_or(RICONCILIAZIONE)
+}
+
+/// Soglia del QBER di test con riconciliazione attiva (variabile `SOGLIA_QBER`, tra 0 e 0.5)
+pub fn soglia_qber() -> f64 {
+	leggi_f64_env("SOGLIA_QBER", SOGLIA_QBER, |q| (0.0..0.5).contains(&q))
+}
+
+/// Restituisce `true` se le chiavi delle sessioni riuscite vanno archiviate (variabile `ATTIVA_ARCHIVIO_CHIAVI`)
+pub fn attiva_archivio_chiavi() -> bool {
+	leggi_bool_env("ATTIVA_ARCHIVIO_CHIAVI", ATTIVA_ARCHIVIO_CHIAVI)
+}
+
+/// Directory dell'archivio delle chiavi (variabile `DIR_ARCHIVIO_CHIAVI`)
+pub fn dir_archivio_chiavi() -> String {
+	std::env::var("DIR_ARCHIVIO_CHIAVI").ok()
+		.filter(|s| !s.is_empty())
+		.unwrap_or_else(|| DIR_ARCHIVIO_CHIAVI.to_string())
+}
+
+/// Identificativo del nodo dello scrittore (variabile `NODO_SCRITTORE`)
+pub fn nodo_scrittore() -> String {
+	leggi_stringa_env("NODO_SCRITTORE", NODO_SCRITTORE)
+}
+
+/// Identificativo del nodo del lettore (variabile `NODO_LETTORE`)
+pub fn nodo_lettore() -> String {
+	leggi_stringa_env("NODO_LETTORE", NODO_LETTORE)
+}
+
+/// Porta del server di consegna chiavi (variabile `PORTA_KME`)
+pub fn porta_kme() -> u16 {
+	std::env::var("PORTA_KME").ok()
+		.and_then(|s| s.parse::<u16>().ok())
+		.unwrap_or(PORTA_KME)
+}
+
+/// Motore delle esecuzioni batch dalla variabile `MOTORE_BATCH` (`oggetti`, `vettoriale`)
+pub fn motore_batch() -> MotoreBatch {
+	std::env::var("MOTORE_BATCH").ok()
+		.and_then(|s| match s.to_lowercase().as_str() {
+			"oggetti" => Some(MotoreBatch::Oggetti),
+			"vettoriale" => Some(MotoreBatch::Vettoriale),
+			_ => None,
+		})
+		.unwrap_or(MOTORE_BATCH)
+}
+
+/// Tipo di collegamento dalla variabile `TIPO_COLLEGAMENTO` (`fibra`, `spazio_libero`)
+pub fn tipo_collegamento() -> TipoCollegamento {
+	std::env::var("TIPO_COLLEGAMENTO").ok()
+		.and_then(|s| match s.to_lowercase().as_str() {
+			"fibra" => Some(TipoCollegamento::Fibra),
+			"spazio_libero" => Some(TipoCollegamento::SpazioLibero),
+			_ => None,
+		})
+		.unwrap_or(TIPO_COLLEGAMENTO)
+}
+
+/// Lunghezza del collegamento in km (variabile `DISTANZA_KM`, >= 0)
+pub fn distanza_km() -> f64 {
+	leggi_f64_env("DISTANZA_KM", DISTANZA_KM, |d| d >= 0.0)
+}
+
+/// Attenuazione in dB/km (variabile `ATTENUAZIONE_DB_KM`); il default dipende dal tipo di collegamento
+pub fn attenuazione_db_km() -> f64 {
+	let default = match tipo_collegamento() {
+		TipoCollegamento::Fibra => ATTENUAZIONE_FIBRA_DB_KM,
+		TipoCollegamento::SpazioLibero => ATTENUAZIONE_SPAZIO_LIBERO_DB_KM,
+	};
+	leggi_f64_env("ATTENUAZIONE_DB_KM", default, |a| a >= 0.0)
+}
+
+/// Perdite di inserzione in dB (variabile `PERDITE_INSERZIONE_DB`, >= 0)
+pub fn perdite_inserzione_db() -> f64 {
+	leggi_f64_env("PERDITE_INSERZIONE_DB", PERDITE_INSERZIONE_DB, |p| p >= 0.0)
+}
+
+/// Efficienza quantica del rivelatore del lettore (variabile `EFFICIENZA_RIVELATORE`, in (0, 1])
+pub fn efficienza_rivelatore() -> f64 {
+	leggi_f64_env("EFFICIENZA_RIVELATORE", EFFICIENZA_RIVELATORE, |e| e > 0.0 && e <= 1.0)
+}
+
+/// Apertura del ricevitore in spazio libero (variabile `DIAMETRO_RICEVITORE_M`, > 0)
+pub fn diametro_ricevitore_m() -> f64 {
+	leggi_f64_env("DIAMETRO_RICEVITORE_M", DIAMETRO_RICEVITORE_M, |d| d > 0.0)
+}
+
+/// Divergenza del fascio in spazio libero in µrad (variabile `DIVERGENZA_URAD`, >= 0)
+pub fn divergenza_urad() -> f64 {
+	leggi_f64_env("DIVERGENZA_URAD", DIVERGENZA_URAD, |d| d >= 0.0)
+}
+
+/// Restituisce `true` se il lettore usa il modello di rivelatore (variabile `ATTIVA_RIVELATORE`).
+/// In questo caso l'efficienza del rivelatore è applicata dal rivelatore e non dal collegamento.
+pub fn attiva_rivelatore() -> bool {
+	leggi_bool_env("ATTIVA_RIVELATORE", ATTIVA_RIVELATORE)
+}
+
+/// Restituisce `true` se anche l'avversario usa il modello di rivelatore (variabile `ATTIVA_RIVELATORE_AVVERSARIO`)
+pub fn attiva_rivelatore_avversario() -> bool {
+	leggi_bool_env("ATTIVA_RIVELATORE_AVVERSARIO", ATTIVA_RIVELATORE_AVVERSARIO)
+}
+
+/// Probabilità di conteggio di buio per slot (variabile `PROB_CONTEGGIO_BUIO`, tra 0 e 1)
+pub fn prob_conteggio_buio() -> f64 {
+	leggi_f64_env("PROB_CONTEGGIO_BUIO", PROB_CONTEGGIO_BUIO, |p| (0.0..=1.0).contains(&p))
+}
+
+/// Tempo morto in slot dopo un click (variabile `TEMPO_MORTO_SLOT`)
+pub fn tempo_morto_slot() -> u32 {
+	std::env::var("TEMPO_MORTO_SLOT").ok()
+		.and_then(|s| s.parse::<u32>().ok())
+		.unwrap_or(TEMPO_MORTO_SLOT)
+}
+
+/// Probabilità di afterpulse (variabile `PROB_AFTERPULSE`, tra 0 e 1)
+pub fn prob_afterpulse() -> f64 {
+	leggi_f64_env("PROB_AFTERPULSE", PROB_AFTERPULSE, |p| (0.0..=1.0).contains(&p))
+}
+
+/// Restituisce `true` se il lettore ha un fotodiodo di sorveglianza (variabile `ATTIVA_FOTODIODO_SORVEGLIANZA`)
+pub fn attiva_fotodiodo_sorveglianza() -> bool {
+	leggi_bool_env("ATTIVA_FOTODIODO_SORVEGLIANZA", ATTIVA_FOTODIODO_SORVEGLIANZA)
+}
+
+/// Frazione di slot con efficienza ridotta per il monitoraggio (variabile `PROB_VERIFICA_EFFICIENZA`, tra 0 e 1)
+pub fn prob_verifica_efficienza() -> f64 {
+	leggi_f64_env("PROB_VERIFICA_EFFICIENZA", PROB_VERIFICA_EFFICIENZA, |p| (0.0..=1.0).contains(&p))
+}
+
+/// `true` se gli attori stampano i messaggi del protocollo a terminale
+pub fn verboso() -> bool {
+	VERBOSO.load(Ordering::Relaxed)
+}
+
+/// Attiva o disattiva i messaggi del protocollo (le esecuzioni batch li disattivano)
+pub fn imposta_verboso(attivo: bool) {
+	VERBOSO.store(attivo, Ordering::Relaxed);
+}
+
+/// Legge una variabile d'ambiente booleana (`1`/`true` oppure `0`/`false`), altrimenti ritorna il default.
+fn leggi_bool_env(nome: &str, default: bool) -> bool {
+	std::env::var(nome).ok()
 		.and_then(|s| match s.as_str() {
 			"1" | "true" | "True" | "TRUE" => Some(true),
 			"0" | "false" | "False" | "FALSE" => Some(false),
 			_ => None,
 		})
-		.unwrap_or(ATTIVA_AVVERSARIO)
+		.unwrap_or(default)
+}
+
+/// Legge un identificativo non vuoto, senza separatori di percorso (è usato nei nomi dei file)
+fn leggi_stringa_env(nome: &str, default: &str) -> String {
+	std::env::var(nome).ok()
+		.filter(|s| !s.is_empty() && !s.contains(['/', '\\']))
+		.unwrap_or_else(|| default.to_string())
+}
+
+/// Legge una variabile d'ambiente numerica; se assente, non numerica o non valida ritorna il default.
+fn leggi_f64_env(nome: &str, default: f64, valido: fn(f64) -> bool) -> f64 {
+	std::env::var(nome).ok()
+		.and_then(|s| s.parse::<f64>().ok())
+		.filter(|v| valido(*v))
+		.unwrap_or(default)
+}
+
+/// Legge il protocollo dalla variabile d'ambiente `PROTOCOLLO` (`BB84`, `BBM92`, `SARG04`, case-insensitive).
+/// Valori non riconosciuti ritornano il default.
+pub fn protocollo() -> Protocollo {
+	std::env::var("PROTOCOLLO").ok()
+		.and_then(|s| match s.to_uppercase().as_str() {
+			"BB84" => Some(Protocollo::BB84),
+			"BBM92" => Some(Protocollo::BBM92),
+			"SARG04" => Some(Protocollo::SARG04),
+			_ => None,
+		})
+		.unwrap_or(PROTOCOLLO)
 }
@@ -0,0 +1,111 @@
+use rand::Rng;
+use crate::config::{fattore_riconciliazione, mu, mu_esca, prob_esca, prob_segnale};
+use crate::information::entropia_binaria;
+use crate::session_rng::generatore;
+
+/// Decoy-state BB84
+/// Lo scrittore sceglie per ogni impulso una classe di intensità (segnale μ, esca ν, vuoto 0)
+/// e la annuncia solo dopo la trasmissione. Confrontando i guadagni delle classi, scrittore e lettore
+/// stimano resa ed errore degli impulsi a singolo fotone, che l'attacco PNS non può falsificare
+/// senza alterare in modo diverso i guadagni delle classi.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClasseIntensita {
+    Segnale,
+    Esca,
+    Vuoto,
+}
+
+impl ClasseIntensita {
+    /// Estrae una classe secondo le probabilità configurate (PROB_SEGNALE, PROB_ESCA, resto vuoto)
+    pub fn casuale() -> Self {
+        let mut rng = generatore();
+        let x: f64 = rng.gen();
+        if x < prob_segnale() {
+            ClasseIntensita::Segnale
+        } else if x < prob_segnale() + prob_esca() {
+            ClasseIntensita::Esca
+        } else {
+            ClasseIntensita::Vuoto
+        }
+    }
+
+    /// Numero medio di fotoni degli impulsi della classe
+    pub fn intensita(&self) -> f64 {
+        match self {
+            ClasseIntensita::Segnale => mu(),
+            ClasseIntensita::Esca => mu_esca(),
+            ClasseIntensita::Vuoto => 0.0,
+        }
+    }
+
+    /// Indice della classe nei vettori di statistiche
+    pub fn indice(&self) -> usize {
+        match self {
+            ClasseIntensita::Segnale => 0,
+            ClasseIntensita::Esca => 1,
+            ClasseIntensita::Vuoto => 2,
+        }
+    }
+}
+
+/// Statistiche pubbliche per classe di intensità (indice: segnale, esca, vuoto)
+#[derive(Debug, Clone, Default)]
+pub struct StatisticheDecoy {
+    pub impulsi: [usize; 3],
+    pub rilevati: [usize; 3],
+    pub bit_confrontati: [usize; 3],
+    pub errori: [usize; 3],
+}
+
+impl StatisticheDecoy {
+    /// Guadagno della classe: frazione di impulsi rilevati
+    pub fn guadagno(&self, classe: ClasseIntensita) -> f64 {
+        let i = classe.indice();
+        if self.impulsi[i] == 0 { 0.0 } else { self.rilevati[i] as f64 / self.impulsi[i] as f64 }
+    }
+
+    /// Tasso d'errore della classe sui bit confrontati pubblicamente
+    pub fn qber(&self, classe: ClasseIntensita) -> f64 {
+        let i = classe.indice();
+        if self.bit_confrontati[i] == 0 { 0.0 } else { self.errori[i] as f64 / self.bit_confrontati[i] as f64 }
+    }
+}
+
+/// Risultato della stima decoy-state (metodo "vuoto + esca debole")
+#[derive(Debug, Clone)]
+pub struct StimaDecoy {
+    pub y0: f64,   // resa degli impulsi vuoti (conteggi di buio)
+    pub y1_l: f64, // limite inferiore della resa a singolo fotone
+    pub q1_l: f64, // limite inferiore del guadagno a singolo fotone negli impulsi segnale
+    pub e1_u: f64, // limite superiore dell'errore a singolo fotone
+    pub lunghezza_chiave: usize,
+}
+
+/// Stima dei parametri a singolo fotone e lunghezza sicura della chiave (formula GLLP con decoy):
+/// Y1 >= μ/(μν - ν²) · (Q_ν e^ν - Q_μ e^μ ν²/μ² - (μ² - ν²)/μ² · Y0)
+/// e1 <= (E_ν Q_ν e^ν - Y0/2) / (Y1 ν)
+/// ℓ = n · [ Q1/Q_μ · (1 - h(e1)) - f · h(E_μ) ]
+/// dove `n` è il numero di bit segnale disponibili per la chiave (dopo il test).
+pub fn stima_parametri(s: &StatisticheDecoy, bit_chiave_segnale: usize) -> StimaDecoy {
+    let (m, v) = (mu(), mu_esca());
+    let q_mu = s.guadagno(ClasseIntensita::Segnale);
+    let q_nu = s.guadagno(ClasseIntensita::Esca);
+    let e_mu = s.qber(ClasseIntensita::Segnale);
+    let e_nu = s.qber(ClasseIntensita::Esca);
+    let y0 = s.guadagno(ClasseIntensita::Vuoto);
+
+    let y1_l = (m / (m * v - v * v)
+        * (q_nu * v.exp() - q_mu * m.exp() * v * v / (m * m) - (m * m - v * v) / (m * m) * y0))
+        .max(0.0);
+    let q1_l = y1_l * m * (-m).exp();
+    let e1_u = if y1_l > 0.0 { ((e_nu * q_nu * v.exp() - 0.5 * y0) / (y1_l * v)).clamp(0.0, 0.5) } else { 0.5 };
+
+    let frazione_sicura = if q_mu > 0.0 {
+        q1_l / q_mu * (1.0 - entropia_binaria(e1_u)) - fattore_riconciliazione() * entropia_binaria(e_mu)
+    } else {
+        0.0
+    };
+    let lunghezza_chiave = ((bit_chiave_segnale as f64) * frazione_sicura.clamp(0.0, 1.0)).floor() as usize;
+
+    StimaDecoy { y0, y1_l, q1_l, e1_u, lunghezza_chiave }
+}
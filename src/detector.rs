@@ -0,0 +1,200 @@
+use rand::Rng;
+use crate::config::{
+    attiva_fotodiodo_sorveglianza, efficienza_rivelatore, prob_afterpulse, prob_conteggio_buio, prob_verifica_efficienza,
+    tempo_morto_slot, FATTORE_VERIFICA_EFFICIENZA,
+};
+use crate::quantum_channel::QuantumChannel;
+use crate::session_rng::generatore;
+
+/// Rivelatore a singolo fotone (coppia di rivelatori, uno per valore 0 e uno per valore 1)
+/// Modella efficienza, conteggi di buio, tempo morto dopo un click e afterpulsing.
+/// Se entrambi i rivelatori scattano nello stesso slot (doppio click) il valore viene assegnato
+/// a caso ("squashing" con assegnazione casuale), come richiesto dalle prove di sicurezza.
+/// Contromisure all'accecamento: un fotodiodo di sorveglianza che segnala luce intensa in ingresso e
+/// la riduzione casuale dell'efficienza in alcuni slot, nei quali i click onesti calano in proporzione
+/// mentre quelli forzati dall'avversario no.
+#[derive(Debug, Clone)]
+pub struct Detector {
+    pub efficienza: f64,
+    pub prob_conteggio_buio: f64,
+    pub tempo_morto_slot: u32,
+    pub prob_afterpulse: f64,
+    tempo_morto_residuo: [u32; 2],  // slot ancora ciechi per ciascun rivelatore
+    afterpulse_in_attesa: [bool; 2], // true se il rivelatore ha scattato e può produrre un afterpulse
+    pub fotodiodo_sorveglianza: bool,
+    pub prob_verifica_efficienza: f64,
+
+    // Statistiche
+    pub click: usize,
+    pub conteggi_buio: usize,
+    pub afterpulse: usize,
+    pub doppi_click: usize,
+    pub slot_tempo_morto: usize,
+    pub click_forzati: usize,     // click prodotti da stati forzati con rivelatori accecati
+    pub allarmi_fotodiodo: usize, // slot con luce intensa segnalata dal fotodiodo di sorveglianza
+    pub slot_normali: usize,
+    pub click_normali: usize,
+    pub slot_verifica: usize,     // slot con efficienza ridotta
+    pub click_verifica: usize,
+}
+
+impl Detector {
+    pub fn new(efficienza: f64, prob_conteggio_buio: f64, tempo_morto_slot: u32, prob_afterpulse: f64) -> Self {
+        assert!((0.0..=1.0).contains(&efficienza));
+        assert!((0.0..=1.0).contains(&prob_conteggio_buio));
+        assert!((0.0..=1.0).contains(&prob_afterpulse));
+        Self {
+            efficienza,
+            prob_conteggio_buio,
+            tempo_morto_slot,
+            prob_afterpulse,
+            tempo_morto_residuo: [0; 2],
+            afterpulse_in_attesa: [false; 2],
+            fotodiodo_sorveglianza: false,
+            prob_verifica_efficienza: 0.0,
+            click: 0,
+            conteggi_buio: 0,
+            afterpulse: 0,
+            doppi_click: 0,
+            slot_tempo_morto: 0,
+            click_forzati: 0,
+            allarmi_fotodiodo: 0,
+            slot_normali: 0,
+            click_normali: 0,
+            slot_verifica: 0,
+            click_verifica: 0,
+        }
+    }
+
+    /// Rivelatore con i parametri configurati
+    pub fn da_config() -> Self {
+        Self::new(efficienza_rivelatore(), prob_conteggio_buio(), tempo_morto_slot(), prob_afterpulse())
+    }
+
+    /// Rivelatore del lettore: parametri configurati e contromisure all'accecamento
+    pub fn da_config_con_contromisure() -> Self {
+        Self {
+            fotodiodo_sorveglianza: attiva_fotodiodo_sorveglianza(),
+            prob_verifica_efficienza: prob_verifica_efficienza(),
+            ..Self::da_config()
+        }
+    }
+
+    /// Misura dell'impulso nel canale con la polarizzazione data.
+    /// Ritorna `Some(valore)` se almeno un rivelatore scatta, `None` se nessuno scatta.
+    pub fn misura(&mut self, q: &mut QuantumChannel, polarizzazione_misura: char) -> Option<u8> {
+        let verifica = self.prob_verifica_efficienza > 0.0 && generatore().gen_bool(self.prob_verifica_efficienza);
+        let esito = self.misura_slot(q, polarizzazione_misura, verifica);
+        if verifica {
+            self.slot_verifica += 1;
+            if esito.is_some() { self.click_verifica += 1; }
+        } else {
+            self.slot_normali += 1;
+            if esito.is_some() { self.click_normali += 1; }
+        }
+        esito
+    }
+
+    /// Misura di un singolo slot; con `verifica` l'efficienza è ridotta di `FATTORE_VERIFICA_EFFICIENZA`
+    fn misura_slot(&mut self, q: &mut QuantumChannel, polarizzazione_misura: char, verifica: bool) -> Option<u8> {
+        let mut rng = generatore();
+
+        // Accecamento: i rivelatori non sono più in modalità a singolo fotone e scattano solo per
+        // l'impulso di innesco dell'avversario, se la base coincide (altrimenti l'impulso si divide
+        // tra i due rivelatori restando sotto soglia). Nessun conteggio di buio né afterpulse.
+        if q.luce_accecante {
+            if self.fotodiodo_sorveglianza {
+                self.allarmi_fotodiodo += 1;
+            }
+            let (pol, valore) = q.canale_quantistico;
+            if pol != polarizzazione_misura {
+                return None;
+            }
+            self.click += 1;
+            self.click_forzati += 1;
+            return Some(valore);
+        }
+
+        let efficienza = if verifica { self.efficienza * FATTORE_VERIFICA_EFFICIENZA } else { self.efficienza };
+        let attivo = [self.tempo_morto_residuo[0] == 0, self.tempo_morto_residuo[1] == 0];
+        if !attivo[0] || !attivo[1] {
+            self.slot_tempo_morto += 1;
+        }
+        let mut scattato = [false; 2];
+
+        // Segnale: ogni fotone è rivelato con probabilità pari all'efficienza
+        if q.fotone_presente() {
+            let valore = q.lettura_fotone(polarizzazione_misura) as usize;
+            let p_rivelazione = 1.0 - (1.0 - efficienza).powi(q.numero_fotoni as i32);
+            if attivo[valore] && rng.gen_bool(p_rivelazione) {
+                scattato[valore] = true;
+            }
+        }
+
+        for (k, click) in scattato.iter_mut().enumerate() {
+            if !attivo[k] {
+                continue;
+            }
+            // Conteggi di buio
+            if !*click && rng.gen_bool(self.prob_conteggio_buio) {
+                *click = true;
+                self.conteggi_buio += 1;
+            }
+            // Afterpulse: possibile nel primo slot attivo dopo un click
+            if self.afterpulse_in_attesa[k] {
+                self.afterpulse_in_attesa[k] = false;
+                if !*click && rng.gen_bool(self.prob_afterpulse) {
+                    *click = true;
+                    self.afterpulse += 1;
+                }
+            }
+        }
+
+        // Aggiornamento del tempo morto
+        for (k, &click) in scattato.iter().enumerate() {
+            if click {
+                self.tempo_morto_residuo[k] = self.tempo_morto_slot;
+                self.afterpulse_in_attesa[k] = true;
+            } else if self.tempo_morto_residuo[k] > 0 {
+                self.tempo_morto_residuo[k] -= 1;
+            }
+        }
+
+        match scattato {
+            [false, false] => None,
+            [true, false] => { self.click += 1; Some(0) }
+            [false, true] => { self.click += 1; Some(1) }
+            [true, true] => {
+                self.click += 1;
+                self.doppi_click += 1;
+                Some(rng.gen_range(0..=1))
+            }
+        }
+    }
+
+    /// Rapporto tra il tasso di click negli slot di verifica e quello negli slot normali
+    /// (circa `FATTORE_VERIFICA_EFFICIENZA` in assenza di accecamento)
+    pub fn rapporto_verifica(&self) -> Option<f64> {
+        if self.slot_verifica == 0 || self.slot_normali == 0 || self.click_normali == 0 {
+            return None;
+        }
+        let tasso_verifica = self.click_verifica as f64 / self.slot_verifica as f64;
+        let tasso_normale = self.click_normali as f64 / self.slot_normali as f64;
+        Some(tasso_verifica / tasso_normale)
+    }
+
+    /// `true` se una contromisura ha rilevato l'accecamento: luce intensa segnalata dal fotodiodo,
+    /// oppure click negli slot di verifica oltre 3 deviazioni standard sopra l'atteso
+    pub fn allarme_accecamento(&self) -> bool {
+        if self.allarmi_fotodiodo > 0 {
+            return true;
+        }
+        if self.slot_verifica == 0 || self.slot_normali == 0 {
+            return false;
+        }
+        let p_attesa = (self.click_normali as f64 / self.slot_normali as f64 * FATTORE_VERIFICA_EFFICIENZA).min(1.0);
+        let attesi = self.slot_verifica as f64 * p_attesa;
+        let deviazione = (attesi * (1.0 - p_attesa)).sqrt();
+        self.click_verifica as f64 > attesi + 3.0 * deviazione.max(1.0)
+    }
+}
@@ -0,0 +1,202 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read, Write};
+use crate::key_store::{impacchetta_bit, ArchivioChiavi, ConsegnaChiavi};
+
+// Cifratura di file o messaggi con le chiavi archiviate.
+// - OTP: XOR del messaggio con tanti bit di chiave quanti sono i suoi bit; i bit sono prelevati
+//   (monouso) dall'archivio, quindi non possono essere riusati, e se non bastano la cifratura fallisce.
+// - AEAD: AES-256-GCM con chiave e nonce derivati con HKDF-SHA256 da BIT_CHIAVE_AEAD bit QKD.
+// La cifratura preleva i bit dagli archivi di scrittore e lettore con un nuovo identificativo (come
+// enc_keys del server di consegna) e lo scrive nell'intestazione del cifrato; la decifratura usa la copia
+// del lettore in consegna con quell'identificativo e la cancella solo se la decifratura riesce.
+// In entrambi i casi la chiave è consumata solo dopo che l'uscita è stata scritta (su file: temporaneo
+// rinominato), così un errore di scrittura non fa perdere né la chiave né il messaggio.
+// Formato: "BB84" | modo (1 = OTP, 2 = AEAD) | identificativo (36 byte) | cifrato (con tag per AEAD).
+
+/// Bit QKD usati come materiale di partenza di HKDF per ogni messaggio AEAD
+const BIT_CHIAVE_AEAD: usize = 256;
+const MAGICO: &[u8; 4] = b"BB84";
+const LUNG_ID: usize = 36;
+const INFO_HKDF: &[u8] = b"bb84_simulazione AES-256-GCM";
+
+/// Modo di cifratura
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModoCifratura {
+    Otp,
+    Aead,
+}
+
+impl ModoCifratura {
+    fn da_nome(nome: &str) -> Option<Self> {
+        match nome {
+            "otp" => Some(Self::Otp),
+            "aead" => Some(Self::Aead),
+            _ => None,
+        }
+    }
+
+    fn codice(self) -> u8 {
+        match self {
+            Self::Otp => 1,
+            Self::Aead => 2,
+        }
+    }
+
+    fn da_codice(codice: u8) -> Option<Self> {
+        match codice {
+            1 => Some(Self::Otp),
+            2 => Some(Self::Aead),
+            _ => None,
+        }
+    }
+}
+
+/// Cifra il messaggio con una chiave estratta dagli archivi; ritorna il cifrato con l'intestazione e la
+/// consegna della chiave, da confermare (consumando i bit) dopo aver scritto il cifrato
+pub fn cifra(modo: ModoCifratura, messaggio: &[u8]) -> io::Result<(Vec<u8>, ConsegnaChiavi)> {
+    let bit_richiesti = match modo {
+        ModoCifratura::Otp => messaggio.len() * 8,
+        ModoCifratura::Aead => BIT_CHIAVE_AEAD,
+    };
+    if bit_richiesti == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "messaggio vuoto"));
+    }
+    let mut consegna = ConsegnaChiavi::apri()?;
+    let Some(voce) = consegna.estrai(bit_richiesti) else {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("chiave troppo corta: richiesti {} bit, disponibili {}", bit_richiesti, consegna.bit_disponibili()),
+        ));
+    };
+    let chiave = impacchetta_bit(&voce.bit);
+
+    let mut cifrato = Vec::with_capacity(MAGICO.len() + 1 + LUNG_ID + messaggio.len() + 16);
+    cifrato.extend_from_slice(MAGICO);
+    cifrato.push(modo.codice());
+    cifrato.extend_from_slice(voce.id.as_bytes());
+    match modo {
+        ModoCifratura::Otp => cifrato.extend(xor(messaggio, &chiave)),
+        ModoCifratura::Aead => {
+            let corpo = aead(&chiave, &voce.id, &cifrato, messaggio, true)?;
+            cifrato.extend(corpo);
+        }
+    }
+    Ok((cifrato, consegna))
+}
+
+/// Decifra con la copia del lettore della chiave indicata nell'intestazione; ritorna il messaggio e
+/// l'identificativo della chiave, da cancellare dalle consegne dopo aver scritto il messaggio
+pub fn decifra(cifrato: &[u8]) -> io::Result<(Vec<u8>, String)> {
+    let dati_non_validi = |messaggio: &str| io::Error::new(io::ErrorKind::InvalidData, messaggio.to_string());
+    let lung_intestazione = MAGICO.len() + 1 + LUNG_ID;
+    if cifrato.len() < lung_intestazione || &cifrato[..MAGICO.len()] != MAGICO {
+        return Err(dati_non_validi("intestazione del cifrato non valida"));
+    }
+    let modo = ModoCifratura::da_codice(cifrato[MAGICO.len()]).ok_or_else(|| dati_non_validi("modo di cifratura sconosciuto"))?;
+    let (intestazione, corpo) = cifrato.split_at(lung_intestazione);
+    let id = std::str::from_utf8(&intestazione[MAGICO.len() + 1..]).map_err(|_| dati_non_validi("identificativo non valido"))?;
+
+    let consegne = ArchivioChiavi::consegne_lettore_da_config()?;
+    let Some(voce) = consegne.voci().iter().find(|v| v.id == id) else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("chiave {} non trovata o già usata", id)));
+    };
+    let chiave = impacchetta_bit(&voce.bit);
+    let messaggio = match modo {
+        ModoCifratura::Otp => {
+            if chiave.len() != corpo.len() {
+                return Err(dati_non_validi("lunghezza della chiave diversa da quella del cifrato"));
+            }
+            xor(corpo, &chiave)
+        }
+        ModoCifratura::Aead => aead(&chiave, id, intestazione, corpo, false)?,
+    };
+    Ok((messaggio, id.to_string()))
+}
+
+fn xor(dati: &[u8], chiave: &[u8]) -> Vec<u8> {
+    dati.iter().zip(chiave).map(|(d, k)| d ^ k).collect()
+}
+
+/// AES-256-GCM con chiave e nonce derivati da HKDF-SHA256 (sale: identificativo della chiave);
+/// l'intestazione è autenticata come dato associato
+fn aead(materiale: &[u8], id: &str, intestazione: &[u8], dati: &[u8], cifratura: bool) -> io::Result<Vec<u8>> {
+    let mut derivato = [0u8; 44];
+    Hkdf::<Sha256>::new(Some(id.as_bytes()), materiale)
+        .expand(INFO_HKDF, &mut derivato)
+        .map_err(|_| io::Error::other("derivazione HKDF non riuscita"))?;
+    let (chiave, nonce) = derivato.split_at(32);
+    let cifrario = Aes256Gcm::new_from_slice(chiave).map_err(|_| io::Error::other("chiave AES non valida"))?;
+    let payload = Payload { msg: dati, aad: intestazione };
+    let esito = if cifratura {
+        cifrario.encrypt(Nonce::from_slice(nonce), payload)
+    } else {
+        cifrario.decrypt(Nonce::from_slice(nonce), payload)
+    };
+    esito.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "autenticazione del cifrato fallita"))
+}
+
+/// Sottocomandi `encrypt <otp|aead> [ingresso] [uscita]` e `decrypt [ingresso] [uscita]`
+/// (`-` o argomento assente: standard input / standard output)
+pub fn comando_cifratura(argomenti: &[String], cifratura: bool) -> io::Result<()> {
+    let (modo, percorsi) = if cifratura {
+        let nome = argomenti.first().map(|s| s.as_str()).unwrap_or("");
+        let modo = ModoCifratura::da_nome(nome).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("modo sconosciuto: {} (modi: otp, aead)", nome))
+        })?;
+        (Some(modo), argomenti.get(1..).unwrap_or(&[]))
+    } else {
+        (None, argomenti)
+    };
+    let ingresso = leggi_ingresso(percorsi.first().map(|s| s.as_str()).unwrap_or("-"))?;
+    let uscita = percorsi.get(1).map(|s| s.as_str()).unwrap_or("-");
+    match modo {
+        Some(modo) => {
+            let (cifrato, consegna) = cifra(modo, &ingresso)?;
+            scrivi_uscita(uscita, &cifrato)?;
+            // Senza la consegna confermata il cifrato non è decifrabile e i bit tornerebbero disponibili:
+            // il file appena scritto viene rimosso
+            consegna.conferma().inspect_err(|_| {
+                if uscita != "-" {
+                    let _ = fs::remove_file(uscita);
+                }
+            })
+        }
+        None => {
+            let (messaggio, id) = decifra(&ingresso)?;
+            scrivi_uscita(uscita, &messaggio)?;
+            ArchivioChiavi::consegne_lettore_da_config()?.preleva_chiave(&id).map(|_| ())
+        }
+    }
+}
+
+/// Scrive l'uscita su standard output (`-`) o su file, passando per un temporaneo (`<file>.tmp`)
+/// rinominato solo a scrittura completata
+fn scrivi_uscita(percorso: &str, dati: &[u8]) -> io::Result<()> {
+    if percorso == "-" {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(dati)?;
+        return stdout.flush();
+    }
+    let mut temporaneo = OsString::from(percorso);
+    temporaneo.push(".tmp");
+    if let Err(e) = fs::write(&temporaneo, dati) {
+        let _ = fs::remove_file(&temporaneo);
+        return Err(e);
+    }
+    fs::rename(&temporaneo, percorso)
+}
+
+fn leggi_ingresso(percorso: &str) -> io::Result<Vec<u8>> {
+    if percorso == "-" {
+        let mut dati = Vec::new();
+        io::stdin().read_to_end(&mut dati)?;
+        Ok(dati)
+    } else {
+        fs::read(percorso)
+    }
+}
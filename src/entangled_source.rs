@@ -0,0 +1,56 @@
+use rand::Rng;
+use crate::config::{POL_X, POL_Z};
+use crate::quantum_channel::QuantumChannel;
+use crate::session_rng::generatore;
+
+/// Sorgente di coppie entangled (protocollo BBM92)
+/// Sorgente esterna e non fidata: per ogni slot spedisce un fotone allo scrittore e uno al lettore.
+/// Una sorgente onesta prepara lo stato |Φ+>: misure nella stessa base danno valori identici,
+/// misure in basi diverse danno valori scorrelati.
+/// Se la sorgente è compromessa la coppia viene preparata dall'avversario (vedi `Adversary`).
+pub struct EntangledSource {
+    pub coppia_entangled: bool, // true se nei canali è presente una coppia entangled non ancora misurata
+    pub coppie_emesse: usize,
+}
+
+impl EntangledSource {
+    pub fn new() -> Self {
+        Self { coppia_entangled: false, coppie_emesse: 0 }
+    }
+
+    /// Emissione di una coppia entangled: un fotone per canale, entrambi con Fotone_IN attivo.
+    /// Finché nessuno misura, lo stato dei canali non ha polarizzazione né valore definiti.
+    pub fn emetti_coppia(&mut self, q_scrittore: &mut QuantumChannel, q_lettore: &mut QuantumChannel) {
+        q_scrittore.canale_quantistico = (' ', 0);
+        q_lettore.canale_quantistico = (' ', 0);
+        q_scrittore.numero_fotoni = 1;
+        q_lettore.numero_fotoni = 1;
+        q_scrittore.set_fotone_in();
+        q_lettore.set_fotone_in();
+        self.coppia_entangled = true;
+        self.coppie_emesse += 1;
+    }
+
+    /// Prima misura sulla coppia (eseguita dallo scrittore sul proprio fotone).
+    /// Se la coppia è entangled il valore è casuale e lo stato collassa su entrambi i canali:
+    /// il fotone del lettore assume la stessa polarizzazione e lo stesso valore.
+    /// Se la coppia non è entangled (sorgente compromessa) la misura è una normale lettura del canale.
+    pub fn misura_primo_fotone(
+        &mut self,
+        q_misurato: &mut QuantumChannel,
+        q_partner: &mut QuantumChannel,
+        polarizzazione_misura: char,
+    ) -> u8 {
+        assert!(polarizzazione_misura == POL_Z || polarizzazione_misura == POL_X);
+        if self.coppia_entangled {
+            let mut rng = generatore();
+            let valore = rng.gen_range(0..=1);
+            q_misurato.canale_quantistico = (polarizzazione_misura, valore);
+            q_partner.canale_quantistico = (polarizzazione_misura, valore);
+            self.coppia_entangled = false;
+            valore
+        } else {
+            q_misurato.lettura_fotone(polarizzazione_misura)
+        }
+    }
+}
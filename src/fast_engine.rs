@@ -0,0 +1,454 @@
+use rand::Rng;
+use std::time::Instant;
+use crate::adversary::Adversary;
+use crate::batch::{esegui_batch_con_motore, StatisticheBatch};
+use crate::bit_vector::{SequenzaFotoni, VettoreBit};
+use crate::config::{
+    attiva_avversario, attiva_decoy, attiva_rivelatore, attiva_rivelatore_avversario, attiva_sorgente_laser,
+    frazione_attacco, lung_msg, motore_batch, mu, prob_base_z, protocollo, strategia_avversario, MotoreBatch, Protocollo,
+    StrategiaAvversario,
+};
+use crate::link_model::LinkModel;
+use crate::public_channel::PublicChannel;
+use crate::reader::Reader;
+use crate::session::{post_elaborazione, Sessione};
+use crate::writer::Writer;
+use crate::session_rng::generatore;
+
+// Motore vettoriale delle sessioni di batch: invece di far passare un fotone alla volta per il canale quantistico,
+// estrae in blocco basi, valori, impulsi attaccati e rilevazioni come vettori di bit e combina le scelte di
+// scrittore, avversario e lettore con operazioni per parole da 64 bit. Le distribuzioni congiunte sono le stesse
+// del motore a oggetti, da cui riprende setaccio e post-elaborazione: il comando `engines` confronta i due motori.
+// Supporta BB84 con sorgente ideale o laser, perdite del collegamento con lettura ideale e intercetta e rispedisci.
+
+/// Sotto questa probabilità (o sopra il complemento) le estrazioni di Bernoulli procedono per salti geometrici
+const SOGLIA_SALTI: f64 = 1.0 / 16.0;
+
+/// Cifre binarie della probabilità usate nelle estrazioni per parole (precisione 2^-32)
+const CIFRE_PROBABILITA: u32 = 32;
+
+/// Motivo per cui la configurazione corrente non è supportata dal motore vettoriale (None se è supportata)
+pub fn configurazione_non_supportata() -> Option<&'static str> {
+    if protocollo() != Protocollo::BB84 {
+        Some("solo BB84")
+    } else if attiva_decoy() {
+        Some("decoy-state non supportato")
+    } else if attiva_rivelatore() || attiva_rivelatore_avversario() {
+        Some("modello di rivelatore non supportato")
+    } else if attiva_avversario() && strategia_avversario() != StrategiaAvversario::IntercettaRispedisci {
+        Some("strategia dell'avversario diversa da intercetta e rispedisci")
+    } else {
+        None
+    }
+}
+
+/// Motore effettivo delle esecuzioni batch: quello configurato, oppure quello a oggetti
+/// se la configurazione non è supportata dal motore vettoriale
+pub fn motore_effettivo() -> MotoreBatch {
+    match motore_batch() {
+        MotoreBatch::Vettoriale if configurazione_non_supportata().is_none() => MotoreBatch::Vettoriale,
+        _ => MotoreBatch::Oggetti,
+    }
+}
+
+/// `n` estrazioni di Bernoulli con probabilità `p`, impacchettate in parole.
+/// Per `p` piccola si salta direttamente al prossimo uno con una distribuzione geometrica (costo proporzionale
+/// agli uni); altrimenti ogni parola combina parole casuali secondo le cifre binarie di `p`: partendo dalla
+/// meno significativa, una cifra 1 fa OR e una cifra 0 fa AND con una parola casuale.
+fn bernoulli(rng: &mut impl Rng, n: usize, p: f64) -> VettoreBit {
+    if p <= 0.0 {
+        return VettoreBit::zeri(n);
+    }
+    if p >= 1.0 {
+        return VettoreBit::zeri(n).negato();
+    }
+    if p > 1.0 - SOGLIA_SALTI {
+        return bernoulli(rng, n, 1.0 - p).negato();
+    }
+    if p < SOGLIA_SALTI {
+        let mut estratti = VettoreBit::zeri(n);
+        let log_fallimento = (-p).ln_1p();
+        let mut i = 0;
+        loop {
+            let u: f64 = rng.gen();
+            let salto = ((1.0 - u).ln() / log_fallimento).floor();
+            if salto >= (n - i) as f64 {
+                return estratti;
+            }
+            i += salto as usize;
+            estratti.imposta(i, true);
+            i += 1;
+        }
+    }
+    let soglia = (p * (1u64 << CIFRE_PROBABILITA) as f64).round() as u64;
+    let parole = (0..n.div_ceil(64))
+        .map(|_| {
+            (soglia.trailing_zeros()..CIFRE_PROBABILITA).fold(0u64, |parola, k| {
+                let casuale: u64 = rng.gen();
+                if soglia >> k & 1 == 1 { parola | casuale } else { parola & casuale }
+            })
+        })
+        .collect();
+    VettoreBit::da_parole(parole, n)
+}
+
+/// Bit per bit: `se_uno` dove la maschera vale uno, `se_zero` altrove
+fn scegli(maschera: &VettoreBit, se_uno: &VettoreBit, se_zero: &VettoreBit) -> VettoreBit {
+    maschera.and(se_uno).or(&maschera.negato().and(se_zero))
+}
+
+/// Parametri letti dal motore vettoriale durante la trasmissione, passati in modo esplicito
+/// (le sessioni di batch li prendono dalla configurazione, i test li costruiscono direttamente)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParametriVettoriali {
+    pub lunghezza: usize,
+    pub prob_base_z: f64,
+    pub mu: Option<f64>,               // None: sorgente ideale a singolo fotone
+    pub frazione_attacco: Option<f64>, // None: nessun avversario (altrimenti intercetta e rispedisci)
+}
+
+impl ParametriVettoriali {
+    pub fn da_config() -> Self {
+        Self {
+            lunghezza: lung_msg(),
+            prob_base_z: prob_base_z(),
+            mu: attiva_sorgente_laser().then(mu),
+            frazione_attacco: attiva_avversario().then(frazione_attacco),
+        }
+    }
+}
+
+/// Trasmissione e setaccio (passi 1-6) con il motore vettoriale: stesso stato finale di `esegui_trasmissione`
+/// (i numeri di fotoni per impulso non sono registrati)
+pub fn esegui_trasmissione_vettoriale(collegamento: &LinkModel, parametri: &ParametriVettoriali) -> Sessione {
+    let mut rng = generatore();
+    let n = parametri.lunghezza;
+    let prob_x = 1.0 - parametri.prob_base_z;
+    let mut canale_pubblico = PublicChannel::con_lunghezza(n);
+    let mut scrittore = Writer::new();
+    let mut lettore = Reader::new();
+    let mut avversario = Adversary::new();
+
+    // 1) Scrittore: basi (Z -> 0, X -> 1) e valori casuali
+    let basi_scrittore = bernoulli(&mut rng, n, prob_x);
+    let valori_scrittore = bernoulli(&mut rng, n, 0.5);
+
+    // 2) Impulsi non vuoti e rilevati. Ogni fotone sopravvive alle perdite con probabilità η: da un impulso
+    //    poissoniano di media μ ne arriva uno con almeno un fotone con probabilità 1 - e^(-μη),
+    //    cioè (1 - e^(-μη)) / (1 - e^(-μ)) tra gli impulsi non vuoti (gli unici che l'avversario può attaccare)
+    let eta = collegamento.trasmittanza().min(1.0);
+    let (non_vuoti, prob_rilevazione) = match parametri.mu {
+        Some(mu) => {
+            let prob_non_vuoto = 1.0 - (-mu).exp();
+            let prob_rilevazione = if prob_non_vuoto > 0.0 { (1.0 - (-mu * eta).exp()) / prob_non_vuoto } else { 0.0 };
+            (bernoulli(&mut rng, n, prob_non_vuoto), prob_rilevazione)
+        }
+        None => (bernoulli(&mut rng, n, 1.0), eta),
+    };
+    let rilevazioni = non_vuoti.and(&bernoulli(&mut rng, n, prob_rilevazione));
+
+    // 3) Avversario: misura gli impulsi attaccati in una base casuale; con base diversa da quella dello scrittore
+    //    il valore collassa a caso e prosegue così verso il lettore
+    let mut valori_canale = valori_scrittore.clone();
+    if let Some(frazione_attacco) = parametri.frazione_attacco {
+        let attaccati = non_vuoti.and(&bernoulli(&mut rng, n, frazione_attacco));
+        let basi_avversario = bernoulli(&mut rng, n, prob_x);
+        let collassati = attaccati.and(&basi_avversario.xor(&basi_scrittore));
+        valori_canale = scegli(&collassati, &bernoulli(&mut rng, n, 0.5), &valori_scrittore);
+        avversario.avversario_messaggio_quantistico_ricevuto =
+            SequenzaFotoni { basi: basi_avversario.and(&attaccati), valori: valori_canale.and(&attaccati) };
+        avversario.impulsi_attaccati = attaccati.conta_uni();
+        avversario.slot_misurati = attaccati;
+    }
+
+    // 4) Lettore: base casuale; con base diversa da quella del fotone il valore è casuale.
+    //    Gli slot senza rilevazione valgono (Z, 0)
+    let basi_lettore = bernoulli(&mut rng, n, prob_x);
+    let valori_lettore = scegli(&basi_lettore.xor(&basi_scrittore), &bernoulli(&mut rng, n, 0.5), &valori_canale);
+    lettore.messaggio_quantistico_ricevuto =
+        SequenzaFotoni { basi: basi_lettore.and(&rilevazioni), valori: valori_lettore.and(&rilevazioni) };
+    lettore.rilevazioni = rilevazioni;
+    scrittore.messaggio_quantistico = SequenzaFotoni { basi: basi_scrittore, valori: valori_scrittore };
+
+    // 5-6) Fine lettura, pubblicazione delle basi e setaccio come nel motore a oggetti
+    lettore.segnala_fine_lettura(&mut canale_pubblico);
+    scrittore.pubblicazione_polarizzazione(&mut canale_pubblico);
+    lettore.elabora_e_invia_esito(&mut canale_pubblico);
+    if canale_pubblico.sequenza_polarizzazioni_pronta {
+        scrittore.selezione_chiave_grezza(&mut canale_pubblico);
+    }
+
+    Sessione { scrittore, lettore, avversario, canale_pubblico, collegamento: collegamento.clone(), riconciliazione: None }
+}
+
+/// Sessione completa con il motore vettoriale: trasmissione vettoriale e post-elaborazione comune (passi 7-9)
+pub fn esegui_sessione_vettoriale(collegamento: &LinkModel) -> Sessione {
+    let mut sessione = esegui_trasmissione_vettoriale(collegamento, &ParametriVettoriali::da_config());
+    sessione.riconciliazione = post_elaborazione(&mut sessione.scrittore, &mut sessione.lettore, &mut sessione.canale_pubblico);
+    sessione
+}
+
+/// Confronto dei due motori sulla configurazione corrente: stesso numero di sessioni, tempi di esecuzione
+/// e scarto delle frazioni stimate in deviazioni standard (test a due proporzioni; oltre 3σ è segnalato)
+pub fn confronta_motori(collegamento: &LinkModel, sessioni: usize) {
+    if let Some(motivo) = configurazione_non_supportata() {
+        eprintln!("Motore vettoriale non disponibile per questa configurazione: {}", motivo);
+        return;
+    }
+    let inizio = Instant::now();
+    let oggetti = esegui_batch_con_motore(collegamento, sessioni, MotoreBatch::Oggetti);
+    let tempo_oggetti = inizio.elapsed().as_secs_f64();
+    let inizio = Instant::now();
+    let vettoriale = esegui_batch_con_motore(collegamento, sessioni, MotoreBatch::Vettoriale);
+    let tempo_vettoriale = inizio.elapsed().as_secs_f64();
+
+    println!("=== Confronto motori ({} sessioni da {} impulsi) ===", sessioni, lung_msg());
+    println!("{:<30} | {:>12} | {:>12} | {:>8} | {:<11}", "Grandezza", "Oggetti", "Vettoriale", "Scarto", "Esito");
+    println!("{}", "-".repeat(86));
+    stampa_confronto("Tasso di rilevazione", (oggetti.rilevati, oggetti.impulsi), (vettoriale.rilevati, vettoriale.impulsi));
+    stampa_confronto(
+        "Bit selezionati per impulso",
+        (oggetti.bit_selezionati, oggetti.impulsi),
+        (vettoriale.bit_selezionati, vettoriale.impulsi),
+    );
+    stampa_confronto("QBER", (oggetti.errori_test, oggetti.bit_test), (vettoriale.errori_test, vettoriale.bit_test));
+    if attiva_avversario() {
+        let indovinati = |s: &StatisticheBatch| (s.conoscenza_avversario.accordo_grezza_avversario, s.conoscenza_avversario.bit_grezzi);
+        stampa_confronto("Chiave indovinata avversario", indovinati(&oggetti), indovinati(&vettoriale));
+    }
+    println!(
+        "{:<30} | {:>12} | {:>12} |",
+        "Sessioni con chiave", oggetti.sessioni_con_chiave, vettoriale.sessioni_con_chiave
+    );
+    println!("{:<30} | {:>12.6} | {:>12.6} |", "Tasso di chiave (bit/impulso)", oggetti.tasso_chiave(), vettoriale.tasso_chiave());
+    println!(
+        "{:<30} | {:>11.3}s | {:>11.3}s | {:>7.1}x",
+        "Tempo di esecuzione",
+        tempo_oggetti,
+        tempo_vettoriale,
+        tempo_oggetti / tempo_vettoriale.max(1e-9)
+    );
+}
+
+/// Riga del confronto per una frazione (successi, prove) stimata dai due motori
+fn stampa_confronto(nome: &str, (k1, n1): (usize, usize), (k2, n2): (usize, usize)) {
+    let scarto = scarto_proporzioni((k1, n1), (k2, n2));
+    let esito = if scarto.abs() <= 3.0 { "compatibile" } else { "DIVERSO" };
+    println!("{:<30} | {:>12.6} | {:>12.6} | {:>7.2}σ | {:<11}", nome, frazione(k1, n1), frazione(k2, n2), scarto, esito);
+}
+
+fn frazione(k: usize, n: usize) -> f64 {
+    if n == 0 { 0.0 } else { k as f64 / n as f64 }
+}
+
+/// Scarto tra due frazioni stimate in deviazioni standard della stima congiunta (test a due proporzioni)
+fn scarto_proporzioni((k1, n1): (usize, usize), (k2, n2): (usize, usize)) -> f64 {
+    let p = frazione(k1 + k2, n1 + n2);
+    let varianza = p * (1.0 - p) * (1.0 / n1.max(1) as f64 + 1.0 / n2.max(1) as f64);
+    if varianza > 0.0 { (frazione(k1, n1) - frazione(k2, n2)) / varianza.sqrt() } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{fissa_seme_principale, imposta_verboso};
+    use crate::session::esegui_trasmissione;
+    use crate::session_rng::imposta_seme_sessione;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Lunghezze con ultima parola parziale (len % 64 != 0)
+    const LUNGHEZZE: [usize; 4] = [1, 63, 65, 100_037];
+
+    /// Tolleranza delle frequenze estratte, in deviazioni standard
+    const SIGMA_BERNOULLI: f64 = 5.0;
+
+    /// Tolleranza del confronto tra i motori, in deviazioni standard
+    const SIGMA_MOTORI: f64 = 4.0;
+
+    /// Vantaggio minimo per impulso della trasmissione vettoriale su quella a oggetti
+    const ACCELERAZIONE_MINIMA: f64 = 5.0;
+
+    /// Gli estratti oltre la lunghezza devono restare a zero: il vettore coincide con quello ricostruito bit per bit
+    fn coda_pulita(v: &VettoreBit) -> bool {
+        *v == v.iter().collect::<VettoreBit>()
+    }
+
+    fn verifica_bernoulli(p: f64) {
+        let mut rng = StdRng::seed_from_u64(84);
+        for n in LUNGHEZZE {
+            let v = bernoulli(&mut rng, n, p);
+            assert_eq!(v.len(), n, "p = {}", p);
+            assert!(coda_pulita(&v), "bit oltre la lunghezza a uno con p = {}, n = {}", p, n);
+            let attesi = n as f64 * p;
+            let sigma = (n as f64 * p * (1.0 - p)).sqrt();
+            let scarto = (v.conta_uni() as f64 - attesi).abs();
+            assert!(
+                scarto <= SIGMA_BERNOULLI * sigma.max(1.0),
+                "p = {}, n = {}: {} uni, attesi {:.1} ± {:.1}",
+                p,
+                n,
+                v.conta_uni(),
+                attesi,
+                sigma
+            );
+        }
+    }
+
+    #[test]
+    fn bernoulli_estremi() {
+        let mut rng = StdRng::seed_from_u64(84);
+        for n in LUNGHEZZE {
+            let zeri = bernoulli(&mut rng, n, 0.0);
+            assert_eq!((zeri.len(), zeri.conta_uni()), (n, 0));
+            let uni = bernoulli(&mut rng, n, 1.0);
+            assert_eq!((uni.len(), uni.conta_uni()), (n, n));
+            assert!(coda_pulita(&zeri) && coda_pulita(&uni));
+        }
+    }
+
+    #[test]
+    fn bernoulli_salti_geometrici() {
+        for p in [1e-4, 0.01, SOGLIA_SALTI / 2.0] {
+            verifica_bernoulli(p);
+        }
+    }
+
+    #[test]
+    fn bernoulli_cifre_binarie() {
+        for p in [SOGLIA_SALTI, 0.3, 0.5, 0.8, 1.0 - SOGLIA_SALTI] {
+            verifica_bernoulli(p);
+        }
+    }
+
+    #[test]
+    fn bernoulli_complemento() {
+        for p in [1.0 - SOGLIA_SALTI / 2.0, 0.99, 1.0 - 1e-4] {
+            verifica_bernoulli(p);
+        }
+    }
+
+    fn verifica_motori(collegamento: &LinkModel, sessioni: usize) {
+        let oggetti = esegui_batch_con_motore(collegamento, sessioni, MotoreBatch::Oggetti);
+        let vettoriale = esegui_batch_con_motore(collegamento, sessioni, MotoreBatch::Vettoriale);
+        let grandezze = [
+            ("tasso di rilevazione", (oggetti.rilevati, oggetti.impulsi), (vettoriale.rilevati, vettoriale.impulsi)),
+            (
+                "bit selezionati per impulso",
+                (oggetti.bit_selezionati, oggetti.impulsi),
+                (vettoriale.bit_selezionati, vettoriale.impulsi),
+            ),
+            ("QBER", (oggetti.errori_test, oggetti.bit_test), (vettoriale.errori_test, vettoriale.bit_test)),
+        ];
+        for (nome, a, b) in grandezze {
+            assert!(a.1 > 0 && b.1 > 0, "{}: nessuna prova", nome);
+            let scarto = scarto_proporzioni(a, b);
+            assert!(
+                scarto.abs() <= SIGMA_MOTORI,
+                "{} (avversario {}): oggetti {:.6}, vettoriale {:.6}, scarto {:.2}σ",
+                nome,
+                attiva_avversario(),
+                frazione(a.0, a.1),
+                frazione(b.0, b.1),
+                scarto
+            );
+        }
+    }
+
+    /// I due motori stimano le stesse frazioni con la configurazione di default (sorgente ideale) su 25 km.
+    /// L'ambiente non viene modificato: i test in parallelo leggono tutti la stessa configurazione.
+    #[test]
+    fn motori_compatibili() {
+        fissa_seme_principale(2024);
+        assert_eq!(configurazione_non_supportata(), None);
+        verifica_motori(&LinkModel::da_config().con_distanza(25.0), 2_000);
+    }
+
+    /// Scarto in deviazioni standard tra la frazione osservata k/n e la probabilità attesa p
+    fn scarto_atteso((k, n): (usize, usize), p: f64) -> f64 {
+        (frazione(k, n) - p) / (p * (1.0 - p) / n.max(1) as f64).sqrt()
+    }
+
+    /// Con parametri espliciti (sorgente laser su 25 km, intercetta e rispedisci su metà degli impulsi)
+    /// il motore vettoriale riproduce le frazioni attese: rilevazioni 1 - e^(-μη), metà dei rilevati nel setaccio,
+    /// QBER f/4 e chiave grezza indovinata dall'avversario 1/2 + f/4
+    #[test]
+    fn vettoriale_frazioni_attese() {
+        let collegamento = LinkModel::da_config().con_distanza(25.0);
+        let eta = collegamento.trasmittanza().min(1.0);
+        for frazione_attacco in [None, Some(0.5)] {
+            let parametri = ParametriVettoriali { lunghezza: 1 << 16, prob_base_z: 0.5, mu: Some(0.5), frazione_attacco };
+            let (mut impulsi, mut rilevati, mut selezionati, mut errori, mut indovinati) = (0, 0, 0, 0, 0);
+            for k in 0..20 {
+                imposta_seme_sessione(k);
+                let s = esegui_trasmissione_vettoriale(&collegamento, &parametri);
+                impulsi += parametri.lunghezza;
+                rilevati += s.lettore.rilevazioni.conta_uni();
+                selezionati += s.scrittore.chiave_grezza.len();
+                errori += s.scrittore.chiave_grezza.differenze(&s.lettore.chiave_grezza);
+                let stima = s.avversario.stima_chiave_grezza(&s.scrittore, &s.canale_pubblico);
+                indovinati += s.scrittore.chiave_grezza.len() - stima.differenze(&s.scrittore.chiave_grezza);
+            }
+            let f = frazione_attacco.unwrap_or(0.0);
+            let grandezze = [
+                ("tasso di rilevazione", (rilevati, impulsi), 1.0 - (-0.5 * eta).exp()),
+                ("bit selezionati per rilevazione", (selezionati, rilevati), 0.5),
+                ("QBER", (errori, selezionati), f / 4.0),
+                ("chiave indovinata avversario", (indovinati, selezionati), 0.5 + f / 4.0),
+            ];
+            for (nome, osservata, attesa) in grandezze {
+                if attesa == 0.0 {
+                    assert_eq!(osservata.0, 0, "{}: attesa nulla", nome);
+                    continue;
+                }
+                let scarto = scarto_atteso(osservata, attesa);
+                assert!(
+                    scarto.abs() <= SIGMA_MOTORI,
+                    "{} (attacco {:?}): osservata {:.6}, attesa {:.6}, scarto {:.2}σ",
+                    nome,
+                    frazione_attacco,
+                    frazione(osservata.0, osservata.1),
+                    attesa,
+                    scarto
+                );
+            }
+        }
+    }
+
+    /// Tempo per impulso della trasmissione a oggetti (sessioni da LUNG_MSG fotoni) e di quella vettoriale
+    /// (una sessione da 2^16 impulsi) con la stessa configurazione: il migliore di tre tentativi per motore
+    /// deve mantenere il vantaggio oltre `ACCELERAZIONE_MINIMA` (misurato circa 25x in debug e 30x in release)
+    #[test]
+    fn accelerazione_vettoriale() {
+        imposta_verboso(false);
+        let collegamento = LinkModel::da_config();
+        let parametri = ParametriVettoriali { lunghezza: 1 << 16, ..ParametriVettoriali::da_config() };
+        let migliore = |esegui: &dyn Fn() -> usize| {
+            (0..3)
+                .map(|_| {
+                    let inizio = Instant::now();
+                    let impulsi = esegui();
+                    inizio.elapsed().as_secs_f64() / impulsi as f64
+                })
+                .fold(f64::INFINITY, f64::min)
+        };
+        let oggetti = migliore(&|| {
+            let mut impulsi = 0;
+            while impulsi < parametri.lunghezza {
+                esegui_trasmissione(&collegamento);
+                impulsi += lung_msg();
+            }
+            impulsi
+        });
+        let vettoriale = migliore(&|| {
+            esegui_trasmissione_vettoriale(&collegamento, &parametri);
+            parametri.lunghezza
+        });
+        assert!(
+            oggetti / vettoriale >= ACCELERAZIONE_MINIMA,
+            "motore vettoriale {:.1}x più rapido, atteso almeno {}x",
+            oggetti / vettoriale,
+            ACCELERAZIONE_MINIMA
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use crate::config::{epsilon_cor, epsilon_sec, fattore_riconciliazione};
+use crate::information::entropia_binaria;
+
+/// Analisi di sicurezza a chiave finita (BB84, limite di Tomamichel et al. 2012)
+/// Con un numero finito di bit l'errore di fase sui bit di chiave non è noto esattamente:
+/// dal campione di test si ottiene un limite superiore Q + δ valido con probabilità 1 - ε_sec,
+/// dove δ = sqrt( (n + k)/(n k) · (k + 1)/k · ln(2/ε_sec) ).
+/// La lunghezza sicura della chiave finale è
+/// ℓ = n · (1 - h(Q + δ)) - leak_EC - log2(2 / (ε_sec² · ε_cor))
+#[derive(Debug, Clone)]
+pub struct StimaChiaveFinita {
+    pub bit_chiave: usize,            // n: bit della chiave grezza rimasti dopo il test
+    pub bit_test: usize,              // k: dimensione del campione di test
+    pub errori_test: usize,           // errori osservati sul campione
+    pub qber_osservato: f64,          // Q = errori / k
+    pub deviazione: f64,              // δ: correzione statistica sul QBER
+    pub perdita_riconciliazione: f64, // leak_EC in bit
+    pub epsilon_sec: f64,
+    pub epsilon_cor: f64,
+    pub lunghezza_chiave: usize,      // ℓ (0 se la chiave non può essere resa sicura)
+}
+
+/// Calcola la lunghezza sicura della chiave finale dai conteggi osservati e dai parametri di sicurezza
+pub fn lunghezza_chiave_sicura(
+    bit_chiave: usize,
+    bit_test: usize,
+    errori_test: usize,
+    perdita_riconciliazione: f64,
+    epsilon_sec: f64,
+    epsilon_cor: f64,
+) -> StimaChiaveFinita {
+    let n = bit_chiave as f64;
+    let k = bit_test as f64;
+    let qber_osservato = if bit_test == 0 { 0.0 } else { errori_test as f64 / k };
+    let deviazione = if bit_chiave == 0 || bit_test == 0 {
+        f64::INFINITY
+    } else {
+        ((n + k) / (n * k) * (k + 1.0) / k * (2.0 / epsilon_sec).ln()).sqrt()
+    };
+
+    // Oltre il 50% di errore di fase non si può estrarre alcun bit sicuro
+    let errore_fase = qber_osservato + deviazione;
+    let lunghezza = if errore_fase >= 0.5 {
+        0.0
+    } else {
+        n * (1.0 - entropia_binaria(errore_fase))
+            - perdita_riconciliazione
+            - (2.0 / (epsilon_sec * epsilon_sec * epsilon_cor)).log2()
+    };
+
+    StimaChiaveFinita {
+        bit_chiave,
+        bit_test,
+        errori_test,
+        qber_osservato,
+        deviazione,
+        perdita_riconciliazione,
+        epsilon_sec,
+        epsilon_cor,
+        lunghezza_chiave: lunghezza.max(0.0).floor() as usize,
+    }
+}
+
+/// Stima con i parametri configurati (ε_sec, ε_cor). La perdita di riconciliazione è quella effettiva
+/// (`bit_rivelati`) se la riconciliazione è stata eseguita, altrimenti la stima f · n · h(Q)
+pub fn stima_da_config(bit_chiave: usize, bit_test: usize, errori_test: usize, bit_rivelati: Option<usize>) -> StimaChiaveFinita {
+    let qber = if bit_test == 0 { 0.0 } else { errori_test as f64 / bit_test as f64 };
+    let perdita = match bit_rivelati {
+        Some(bit) => bit as f64,
+        None => fattore_riconciliazione() * bit_chiave as f64 * entropia_binaria(qber),
+    };
+    lunghezza_chiave_sicura(bit_chiave, bit_test, errori_test, perdita, epsilon_sec(), epsilon_cor())
+}
@@ -0,0 +1,16 @@
+// Funzioni di teoria dell'informazione usate nelle stime di sicurezza
+
+/// Entropia binaria h(p) = -p log2(p) - (1-p) log2(1-p), con h(0) = h(1) = 0
+pub fn entropia_binaria(p: f64) -> f64 {
+    if p <= 0.0 || p >= 1.0 {
+        0.0
+    } else {
+        -p * p.log2() - (1.0 - p) * (1.0 - p).log2()
+    }
+}
+
+/// Informazione mutua per bit tra due stringhe che differiscono con probabilità `errore`
+/// (canale binario simmetrico): I = 1 - h(errore)
+pub fn informazione_mutua_bsc(errore: f64) -> f64 {
+    1.0 - entropia_binaria(errore)
+}
@@ -0,0 +1,102 @@
+use crate::batch::esegui_batch;
+use crate::config::{attiva_rivelatore, fattore_riconciliazione, mu, prob_base_z, prob_conteggio_buio};
+use crate::information::entropia_binaria;
+use crate::link_model::LinkModel;
+
+/// Parametri del calcolo analitico del tasso di chiave asintotico (bit per impulso spedito)
+#[derive(Debug, Clone)]
+pub struct ParametriTassoChiave {
+    pub qber: f64,                    // E: QBER complessivo osservato
+    pub fattore_sifting: f64,         // q: frazione di impulsi con basi coincidenti
+    pub mu: f64,                      // intensità media della sorgente laser
+    pub trasmittanza: f64,            // η: canale ed efficienza del rivelatore
+    pub resa_vuoto: f64,              // Y0: probabilità di rilevazione senza fotoni (conteggi di buio)
+    pub fattore_riconciliazione: f64, // f: inefficienza della correzione errori
+}
+
+impl ParametriTassoChiave {
+    /// Parametri del collegamento e della configurazione correnti, con il QBER indicato.
+    /// Y0 conta i conteggi di buio di entrambi i rivelatori; senza modello di rivelatore è nullo.
+    pub fn da_config(collegamento: &LinkModel, qber: f64) -> Self {
+        let p = prob_base_z();
+        Self {
+            qber,
+            fattore_sifting: p * p + (1.0 - p) * (1.0 - p),
+            mu: mu(),
+            trasmittanza: collegamento.trasmittanza(),
+            resa_vuoto: if attiva_rivelatore() { 2.0 * prob_conteggio_buio() } else { 0.0 },
+            fattore_riconciliazione: fattore_riconciliazione(),
+        }
+    }
+
+    /// Guadagno degli impulsi coerenti: Q_μ = Y0 + 1 - e^(-ημ)
+    pub fn guadagno_segnale(&self) -> f64 {
+        (self.resa_vuoto + 1.0 - (-self.trasmittanza * self.mu).exp()).min(1.0)
+    }
+
+    /// Shor–Preskill con sorgente ideale a singolo fotone:
+    /// R = q · Q · [1 - h(E) - f · h(E)], con Q = Y0 + η - Y0·η
+    pub fn tasso_shor_preskill(&self) -> f64 {
+        let guadagno = self.resa_vuoto + self.trasmittanza - self.resa_vuoto * self.trasmittanza;
+        let h = entropia_binaria(self.qber);
+        (self.fattore_sifting * guadagno * (1.0 - h - self.fattore_riconciliazione * h)).max(0.0)
+    }
+
+    /// GLLP con impulsi coerenti senza decoy: tutti gli impulsi multi-fotone sono considerati noti all'avversario.
+    /// Δ = P(n >= 2) / Q_μ, R = q · Q_μ · [(1 - Δ)(1 - h(E / (1 - Δ))) - f · h(E)]
+    pub fn tasso_gllp(&self) -> f64 {
+        let q_mu = self.guadagno_segnale();
+        if q_mu <= 0.0 {
+            return 0.0;
+        }
+        let p_multi = 1.0 - (-self.mu).exp() * (1.0 + self.mu);
+        let frazione_singoli = 1.0 - p_multi / q_mu;
+        if frazione_singoli <= 0.0 {
+            return 0.0;
+        }
+        let errore_singoli = (self.qber / frazione_singoli).min(0.5);
+        let r = frazione_singoli * (1.0 - entropia_binaria(errore_singoli))
+            - self.fattore_riconciliazione * entropia_binaria(self.qber);
+        (self.fattore_sifting * q_mu * r).max(0.0)
+    }
+
+    /// GLLP con decoy-state ideale (infinite intensità esca): Y1 = Y0 + η, Q1 = Y1 · μ e^(-μ),
+    /// e1 = (Y0/2 + e_ott · η) / Y1, con l'errore ottico e_ott ricavato dal QBER complessivo.
+    /// R = q · [Q1 · (1 - h(e1)) - Q_μ · f · h(E)]
+    pub fn tasso_gllp_decoy(&self) -> f64 {
+        let q_mu = self.guadagno_segnale();
+        let y1 = (self.resa_vuoto + self.trasmittanza).min(1.0);
+        if q_mu <= 0.0 || y1 <= 0.0 {
+            return 0.0;
+        }
+        let frazione_segnale = 1.0 - (-self.trasmittanza * self.mu).exp();
+        let errore_ottico = if frazione_segnale > 0.0 {
+            ((self.qber * q_mu - 0.5 * self.resa_vuoto) / frazione_segnale).clamp(0.0, 0.5)
+        } else {
+            0.5
+        };
+        let q1 = y1 * self.mu * (-self.mu).exp();
+        let e1 = ((0.5 * self.resa_vuoto + errore_ottico * self.trasmittanza) / y1).min(0.5);
+        let r = q1 * (1.0 - entropia_binaria(e1)) - q_mu * self.fattore_riconciliazione * entropia_binaria(self.qber);
+        (self.fattore_sifting * r).max(0.0)
+    }
+}
+
+/// Confronta i tassi asintotici con quello misurato su un batch di `sessioni` sessioni simulate.
+/// Se `qber` non è indicato si usa il QBER misurato nel batch.
+pub fn confronta_con_batch(collegamento: &LinkModel, sessioni: usize, qber: Option<f64>) {
+    let statistiche = esegui_batch(collegamento, sessioni);
+    let parametri = ParametriTassoChiave::da_config(collegamento, qber.unwrap_or(statistiche.qber()));
+
+    println!("=== Tasso di chiave asintotico ({:?}, {:.1} km) ===", collegamento.tipo, collegamento.distanza_km);
+    println!("QBER                                  : {:>13.4}", parametri.qber);
+    println!("Fattore di sifting q                  : {:>13.4}", parametri.fattore_sifting);
+    println!("Intensità sorgente mu                 : {:>13.4}", parametri.mu);
+    println!("Trasmittanza eta                      : {:>13.4e}", parametri.trasmittanza);
+    println!("Resa impulsi vuoti Y0                 : {:>13.4e}", parametri.resa_vuoto);
+    println!("{}", "-".repeat(52));
+    println!("Shor-Preskill (singolo fotone)        : {:>13.6}", parametri.tasso_shor_preskill());
+    println!("GLLP (impulsi coerenti, senza decoy)  : {:>13.6}", parametri.tasso_gllp());
+    println!("GLLP con decoy-state ideale           : {:>13.6}", parametri.tasso_gllp_decoy());
+    println!("Simulato ({:>5} sessioni)             : {:>13.6}", statistiche.sessioni, statistiche.tasso_chiave());
+}
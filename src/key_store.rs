@@ -0,0 +1,355 @@
+use rand::Rng;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::bit_vector::VettoreBit;
+use crate::config::{dir_archivio_chiavi, epsilon_cor, epsilon_sec, nodo_lettore, nodo_scrittore};
+use crate::session::Sessione;
+
+// Archivio persistente delle chiavi generate.
+// Ogni nodo ha un file per ciascun nodo remoto (`<locale>__<remoto>.chiavi`) con una riga per chiave:
+// identificativo, istante di generazione, QBER, parametri di sicurezza e bit della chiave.
+// Scrittore e lettore archiviano la propria copia con lo stesso identificativo.
+// Il prelievo ha semantica monouso: i bit consegnati sono cancellati dal file.
+// Le operazioni che toccano più file (scrittore, lettore, consegne) li scrivono prima tutti su file temporanei
+// e li sostituiscono solo se le scritture sono riuscite, così i due lati restano allineati.
+
+/// Chiave archiviata con i metadati della sessione che l'ha generata
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoceChiave {
+    pub id: String,     // UUID (versione 4) comune alle copie di scrittore e lettore
+    pub istante: u64,   // secondi dall'epoca Unix
+    pub qber: f64,      // QBER stimato sui bit di test
+    pub epsilon_sec: f64,
+    pub epsilon_cor: f64,
+    pub bit: Vec<u8>,
+}
+
+impl VoceChiave {
+    /// Nuova voce con identificativo casuale, istante corrente e parametri ε configurati
+    pub fn nuova(bit: Vec<u8>, qber: f64) -> Self {
+        Self { id: nuovo_id(), istante: istante_corrente(), qber, epsilon_sec: epsilon_sec(), epsilon_cor: epsilon_cor(), bit }
+    }
+
+    fn in_riga(&self) -> String {
+        let bit: String = self.bit.iter().map(|&b| if b == 1 { '1' } else { '0' }).collect();
+        format!("{}\t{}\t{}\t{:e}\t{:e}\t{}", self.id, self.istante, self.qber, self.epsilon_sec, self.epsilon_cor, bit)
+    }
+
+    fn da_riga(riga: &str) -> Option<Self> {
+        let campi: Vec<&str> = riga.split('\t').collect();
+        if campi.len() != 6 {
+            return None;
+        }
+        let bit = campi[5]
+            .chars()
+            .map(|c| match c {
+                '0' => Some(0),
+                '1' => Some(1),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()?;
+        Some(Self {
+            id: campi[0].to_string(),
+            istante: campi[1].parse().ok()?,
+            qber: campi[2].parse().ok()?,
+            epsilon_sec: campi[3].parse().ok()?,
+            epsilon_cor: campi[4].parse().ok()?,
+            bit,
+        })
+    }
+}
+
+/// Archivio delle chiavi condivise tra il nodo locale e un nodo remoto
+#[derive(Debug)]
+pub struct ArchivioChiavi {
+    pub percorso: PathBuf,
+    voci: Vec<VoceChiave>,
+}
+
+impl ArchivioChiavi {
+    /// Apre (o crea vuoto) l'archivio del nodo `locale` verso il nodo `remoto` nella directory indicata
+    pub fn apri(directory: &Path, locale: &str, remoto: &str) -> io::Result<Self> {
+        Self::apri_file(directory.join(format!("{}__{}.chiavi", locale, remoto)))
+    }
+
+    /// Apre (o crea vuoto) l'archivio nel file indicato
+    fn apri_file(percorso: PathBuf) -> io::Result<Self> {
+        let voci = match fs::read_to_string(&percorso) {
+            Ok(contenuto) => contenuto
+                .lines()
+                .filter(|r| !r.is_empty() && !r.starts_with('#'))
+                .map(|r| {
+                    VoceChiave::da_riga(r).ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, format!("riga non valida in {}", percorso.display()))
+                    })
+                })
+                .collect::<io::Result<Vec<_>>>()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self { percorso, voci })
+    }
+
+    /// Archivio del nodo dello scrittore verso il lettore (nodi e directory configurati)
+    pub fn scrittore_da_config() -> io::Result<Self> {
+        Self::apri(Path::new(&dir_archivio_chiavi()), &nodo_scrittore(), &nodo_lettore())
+    }
+
+    /// Archivio del nodo del lettore verso lo scrittore (nodi e directory configurati)
+    pub fn lettore_da_config() -> io::Result<Self> {
+        Self::apri(Path::new(&dir_archivio_chiavi()), &nodo_lettore(), &nodo_scrittore())
+    }
+
+    /// Chiavi già consegnate al nodo dello scrittore e in attesa di essere richieste dal lettore
+    /// tramite identificativo (`<lettore>__<scrittore>.consegnate`)
+    pub fn consegne_lettore_da_config() -> io::Result<Self> {
+        Self::apri_file(Path::new(&dir_archivio_chiavi()).join(format!("{}__{}.consegnate", nodo_lettore(), nodo_scrittore())))
+    }
+
+    pub fn voci(&self) -> &[VoceChiave] {
+        &self.voci
+    }
+
+    /// Bit disponibili in tutte le chiavi dell'archivio
+    pub fn bit_disponibili(&self) -> usize {
+        self.voci.iter().map(|v| v.bit.len()).sum()
+    }
+
+    /// Preleva l'intera chiave con l'identificativo indicato e la cancella dall'archivio
+    pub fn preleva_chiave(&mut self, id: &str) -> io::Result<Option<VoceChiave>> {
+        let voce = self.estrai_chiave(id);
+        if voce.is_some() {
+            self.salva()?;
+        }
+        Ok(voce)
+    }
+
+    /// Toglie la chiave con l'identificativo indicato dall'archivio in memoria (senza salvarlo)
+    fn estrai_chiave(&mut self, id: &str) -> Option<VoceChiave> {
+        let posizione = self.voci.iter().position(|v| v.id == id)?;
+        Some(self.voci.remove(posizione))
+    }
+
+    /// Toglie `n` bit dall'archivio in memoria (senza salvarlo) partendo dalle chiavi più vecchie: una chiave
+    /// consumata in parte conserva i bit restanti con lo stesso identificativo. Se i bit non bastano non toglie nulla.
+    fn estrai_bit(&mut self, n: usize) -> Option<Vec<u8>> {
+        if n > self.bit_disponibili() {
+            return None;
+        }
+        let mut bit = Vec::with_capacity(n);
+        while bit.len() < n {
+            let voce = &mut self.voci[0];
+            let presi = (n - bit.len()).min(voce.bit.len());
+            bit.extend(voce.bit.drain(..presi));
+            if voce.bit.is_empty() {
+                self.voci.remove(0);
+            }
+        }
+        Some(bit)
+    }
+
+    /// Scrive l'archivio e lo sostituisce a quello precedente
+    fn salva(&self) -> io::Result<()> {
+        salva_insieme(&[self])
+    }
+
+    /// Scrive l'archivio accanto al file originale (`<file>.tmp`) e ritorna il percorso del temporaneo
+    fn scrivi_temporaneo(&self) -> io::Result<PathBuf> {
+        if let Some(directory) = self.percorso.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let mut contenuto = String::from("# id\tistante\tqber\tepsilon_sec\tepsilon_cor\tbit\n");
+        for voce in &self.voci {
+            contenuto.push_str(&voce.in_riga());
+            contenuto.push('\n');
+        }
+        let mut temporaneo = self.percorso.clone().into_os_string();
+        temporaneo.push(".tmp");
+        let temporaneo = PathBuf::from(temporaneo);
+        fs::write(&temporaneo, contenuto)?;
+        Ok(temporaneo)
+    }
+}
+
+/// Salva più archivi insieme: scrive tutti i file temporanei e li sostituisce agli originali solo se
+/// sono stati scritti tutti; in caso di errore i file originali restano quelli di prima
+fn salva_insieme(archivi: &[&ArchivioChiavi]) -> io::Result<()> {
+    let mut temporanei = Vec::with_capacity(archivi.len());
+    for archivio in archivi {
+        match archivio.scrivi_temporaneo() {
+            Ok(temporaneo) => temporanei.push(temporaneo),
+            Err(e) => {
+                for temporaneo in &temporanei {
+                    let _ = fs::remove_file(temporaneo);
+                }
+                return Err(e);
+            }
+        }
+    }
+    for (archivio, temporaneo) in archivi.iter().zip(&temporanei) {
+        fs::rename(temporaneo, &archivio.percorso)?;
+    }
+    Ok(())
+}
+
+/// Archivi di scrittore e lettore (configurati), rifiutati se i bit disponibili non coincidono
+fn archivi_allineati() -> io::Result<(ArchivioChiavi, ArchivioChiavi)> {
+    let scrittore = ArchivioChiavi::scrittore_da_config()?;
+    let lettore = ArchivioChiavi::lettore_da_config()?;
+    if lettore.bit_disponibili() != scrittore.bit_disponibili() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "archivi di scrittore e lettore non allineati"));
+    }
+    Ok((scrittore, lettore))
+}
+
+/// Archivia la chiave di una sessione riuscita negli archivi di scrittore e lettore con lo stesso
+/// identificativo. Ritorna l'identificativo assegnato.
+pub fn archivia_chiave(chiave_scrittore: &VettoreBit, chiave_lettore: &VettoreBit, qber: f64) -> io::Result<String> {
+    let voce = VoceChiave::nuova(chiave_scrittore.in_bit(), qber);
+    let voce_lettore = VoceChiave { bit: chiave_lettore.in_bit(), ..voce.clone() };
+    let mut scrittore = ArchivioChiavi::scrittore_da_config()?;
+    let mut lettore = ArchivioChiavi::lettore_da_config()?;
+    scrittore.voci.push(voce.clone());
+    lettore.voci.push(voce_lettore);
+    salva_insieme(&[&scrittore, &lettore])?;
+    Ok(voce.id)
+}
+
+/// Consegne preparate in memoria sugli archivi di scrittore e lettore e sulle consegne in attesa:
+/// i bit estratti sono consumati solo da `conferma`, che salva i tre file insieme. Se la consegna
+/// viene abbandonata (ad esempio perché il cifrato non è stato scritto) gli archivi restano invariati.
+pub struct ConsegnaChiavi {
+    scrittore: ArchivioChiavi,
+    lettore: ArchivioChiavi,
+    consegne: ArchivioChiavi,
+}
+
+impl ConsegnaChiavi {
+    /// Apre gli archivi configurati (rifiutati se non allineati)
+    pub fn apri() -> io::Result<Self> {
+        let (scrittore, lettore) = archivi_allineati()?;
+        Ok(Self { scrittore, lettore, consegne: ArchivioChiavi::consegne_lettore_da_config()? })
+    }
+
+    /// Bit ancora disponibili per nuove consegne
+    pub fn bit_disponibili(&self) -> usize {
+        self.scrittore.bit_disponibili()
+    }
+
+    /// Estrae `n` bit con un nuovo identificativo e mette la copia del lettore tra le consegne in attesa.
+    /// Ritorna la copia dello scrittore, oppure `None` se i bit disponibili non bastano.
+    pub fn estrai(&mut self, n: usize) -> Option<VoceChiave> {
+        let origine = self.scrittore.voci().first().cloned()?;
+        let (Some(bit_scrittore), Some(bit_lettore)) = (self.scrittore.estrai_bit(n), self.lettore.estrai_bit(n)) else {
+            return None;
+        };
+        let voce = VoceChiave { id: nuovo_id(), bit: bit_scrittore, ..origine };
+        self.consegne.voci.push(VoceChiave { bit: bit_lettore, ..voce.clone() });
+        Some(voce)
+    }
+
+    /// Consuma i bit estratti: salva insieme archivi e consegne
+    pub fn conferma(self) -> io::Result<()> {
+        salva_insieme(&[&self.scrittore, &self.lettore, &self.consegne])
+    }
+}
+
+/// Bit in byte, dal più significativo (la lunghezza è un multiplo di 8)
+pub fn impacchetta_bit(bit: &[u8]) -> Vec<u8> {
+    bit.chunks(8).map(|ottetto| ottetto.iter().fold(0u8, |acc, &b| (acc << 1) | b)).collect()
+}
+
+/// Identificativo casuale nel formato UUID versione 4
+pub fn nuovo_id() -> String {
+    let mut byte: [u8; 16] = rand::thread_rng().gen();
+    byte[6] = (byte[6] & 0x0f) | 0x40;
+    byte[8] = (byte[8] & 0x3f) | 0x80;
+    let esadecimale: String = byte.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &esadecimale[0..8],
+        &esadecimale[8..12],
+        &esadecimale[12..16],
+        &esadecimale[16..20],
+        &esadecimale[20..32]
+    )
+}
+
+fn istante_corrente() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Archivia la chiave di una sessione riuscita (nessuna azione se la chiave non è stata definita)
+pub fn archivia_sessione(s: &Sessione) -> io::Result<Option<String>> {
+    if !s.canale_pubblico.chiave_simmetrica_ok || s.scrittore.chiave_simmetrica.is_empty() {
+        return Ok(None);
+    }
+    let bit_test = s.scrittore.test_avversario.len();
+    let qber = if bit_test == 0 { 0.0 } else { s.canale_pubblico.errori_test as f64 / bit_test as f64 };
+    archivia_chiave(&s.scrittore.chiave_simmetrica, &s.lettore.chiave_simmetrica, qber).map(Some)
+}
+
+/// Sottocomando `keystore`: `elenco` (default), `preleva <bit> [scrittore|lettore]`,
+/// `preleva_id <id> [scrittore|lettore]`. Il prelievo consuma sempre entrambi gli archivi
+/// e stampa la copia del lato indicato (scrittore per default).
+pub fn comando_archivio(argomenti: &[String]) -> io::Result<()> {
+    let copia_lato = |scrittore: Vec<u8>, lettore: Vec<u8>| match argomenti.get(2).map(|s| s.as_str()) {
+        Some("lettore") => lettore,
+        _ => scrittore,
+    };
+    let stampa_bit = |bit: &[u8]| println!("{}", bit.iter().map(|b| b.to_string()).collect::<String>());
+    match argomenti.first().map(|s| s.as_str()) {
+        None | Some("elenco") => {
+            for archivio in [ArchivioChiavi::scrittore_da_config()?, ArchivioChiavi::lettore_da_config()?] {
+                stampa_archivio(&archivio);
+            }
+        }
+        Some("preleva") => {
+            let n = argomenti.get(1).and_then(|s| s.parse::<usize>().ok()).unwrap_or(256);
+            let (mut scrittore, mut lettore) = archivi_allineati()?;
+            let disponibili = scrittore.bit_disponibili();
+            let (Some(bit_scrittore), Some(bit_lettore)) = (scrittore.estrai_bit(n), lettore.estrai_bit(n)) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("bit insufficienti: richiesti {}, disponibili {}", n, disponibili),
+                ));
+            };
+            salva_insieme(&[&scrittore, &lettore])?;
+            stampa_bit(&copia_lato(bit_scrittore, bit_lettore));
+        }
+        Some("preleva_id") => {
+            let id = argomenti.get(1).map(|s| s.as_str()).unwrap_or("");
+            let (mut scrittore, mut lettore) = archivi_allineati()?;
+            let (Some(voce_scrittore), Some(voce_lettore)) = (scrittore.estrai_chiave(id), lettore.estrai_chiave(id)) else {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("chiave {} non trovata", id)));
+            };
+            salva_insieme(&[&scrittore, &lettore])?;
+            stampa_bit(&copia_lato(voce_scrittore.bit, voce_lettore.bit));
+        }
+        Some(altro) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("azione sconosciuta: {} (azioni: elenco, preleva [bit] [lato], preleva_id <id> [lato])", altro),
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn stampa_archivio(archivio: &ArchivioChiavi) {
+    println!("
+=== Archivio {} ({} chiavi, {} bit) ===", archivio.percorso.display(), archivio.voci().len(), archivio.bit_disponibili());
+    if archivio.voci().is_empty() {
+        return;
+    }
+    println!("{:<36} | {:>10} | {:>7} | {:>9} | {:>9} | {:>7}", "Id", "Istante", "QBER", "eps_sec", "eps_cor", "Bit");
+    println!("{}", "-".repeat(94));
+    for v in archivio.voci() {
+        println!(
+            "{:<36} | {:>10} | {:>6.2}% | {:>9.0e} | {:>9.0e} | {:>7}",
+            v.id, v.istante, v.qber * 100.0, v.epsilon_sec, v.epsilon_cor, v.bit.len()
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use crate::bit_vector::VettoreBit;
+use crate::config::epsilon_cor;
+use crate::privacy_amplification::{comprimi_chiave, genera_seme};
+
+// Verifica della chiave dopo la riconciliazione con hashing universale (stessa famiglia di Toeplitz
+// dell'amplificazione della privacy). Lo scrittore pubblica un seme casuale e il tag della propria chiave,
+// il lettore calcola il tag della sua: chiavi diverse producono lo stesso tag con probabilità al più 2^-t.
+// Il tag rivela t bit della chiave: t è limitato a un terzo della chiave (sulle chiavi corte la probabilità
+// di collisione riportata è quindi più alta di ε_cor). Con l'amplificazione della privacy i t bit sono sottratti
+// come la perdita di riconciliazione: nella stima decoy-state esplicitamente, nell'analisi a chiave finita nel
+// termine log2(1/ε_cor). Senza amplificazione la chiave resta quella riconciliata. Una chiave di meno di
+// `FRAZIONE_MASSIMA_TAG` bit non ammette alcun tag e non può essere verificata.
+
+/// Il tag rivela al più un bit della chiave ogni `FRAZIONE_MASSIMA_TAG`
+const FRAZIONE_MASSIMA_TAG: usize = 3;
+
+/// Tag di verifica pubblicato dallo scrittore
+#[derive(Debug, Clone, Default)]
+pub struct TagVerifica {
+    pub seme: VettoreBit,
+    pub tag: VettoreBit,
+}
+
+impl TagVerifica {
+    /// Tag della chiave dello scrittore: ⌈log2(1/ε_cor)⌉ bit, al più un terzo della chiave.
+    /// Ritorna `None` se la chiave è troppo corta per un tag significativo.
+    pub fn genera(chiave: &VettoreBit) -> Option<Self> {
+        let bit = lunghezza_tag(chiave.len())?;
+        let seme = genera_seme(chiave.len(), bit);
+        let tag = comprimi_chiave(chiave, bit, &seme);
+        Some(Self { seme, tag })
+    }
+
+    /// Confronta il tag con quello della chiave del lettore calcolato con lo stesso seme
+    pub fn verifica(&self, chiave: &VettoreBit) -> bool {
+        if self.seme.len() != (chiave.len() + self.tag.len()).saturating_sub(1) {
+            return false;
+        }
+        comprimi_chiave(chiave, self.tag.len(), &self.seme) == self.tag
+    }
+
+    /// Probabilità che due chiavi diverse superino la verifica (2^-t)
+    pub fn prob_collisione(&self) -> f64 {
+        0.5f64.powi(self.tag.len() as i32)
+    }
+}
+
+/// Lunghezza del tag per una chiave di `bit_chiave` bit: quella che garantisce la correttezza ε_cor configurata,
+/// limitata a un terzo della chiave. `None` se la chiave è troppo corta anche per un tag di un bit.
+pub fn lunghezza_tag(bit_chiave: usize) -> Option<usize> {
+    let richiesta = (1.0 / epsilon_cor()).log2().ceil() as usize;
+    Some(richiesta.min(bit_chiave / FRAZIONE_MASSIMA_TAG)).filter(|&bit| bit > 0)
+}
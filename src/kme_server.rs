@@ -0,0 +1,299 @@
+use std::collections::HashSet;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use crate::config::{nodo_lettore, nodo_scrittore};
+use crate::key_store::{archivia_chiave, impacchetta_bit, ArchivioChiavi, ConsegnaChiavi};
+use crate::link_model::LinkModel;
+use crate::target_key::esegui_chiave_obiettivo;
+
+// Server di consegna chiavi in stile ETSI GS QKD 014 (REST su HTTP, solo localhost, senza TLS).
+// Il nodo dello scrittore fa da KME del SAE master, quello del lettore da KME del SAE slave:
+// - GET        /api/v1/keys/{slave_SAE_ID}/status
+// - GET | POST /api/v1/keys/{slave_SAE_ID}/enc_keys  (number, size): nuove chiavi con identificativo
+// - GET | POST /api/v1/keys/{master_SAE_ID}/dec_keys  (key_ID): le stesse chiavi lato slave
+// I bit sono prelevati (monouso) dagli archivi di scrittore e lettore; se non bastano il server
+// esegue sessioni del simulatore in modalità chiave obiettivo e archivia le chiavi prodotte.
+// Le richieste sono servite una alla volta, senza concorrenza.
+
+const DIM_CHIAVE_DEFAULT: usize = 256;
+const DIM_CHIAVE_MIN: usize = 64;
+const DIM_CHIAVE_MAX: usize = 4096;
+const MAX_CHIAVI_RICHIESTA: usize = 128;
+const MAX_CHIAVI_ARCHIVIO: usize = 100_000;
+const ROUND_MAX_RICARICA: usize = 100_000;
+/// Dimensione massima del corpo di una richiesta (oltre si risponde 413 senza leggerlo)
+const MAX_CORPO_RICHIESTA: usize = 64 * 1024;
+
+/// Richiesta HTTP essenziale: metodo, percorso, parametri della query e corpo
+struct Richiesta {
+    metodo: String,
+    percorso: String,
+    query: Vec<(String, String)>,
+    corpo: String,
+}
+
+impl Richiesta {
+    fn parametro(&self, nome: &str) -> Option<&str> {
+        self.query.iter().find(|(k, _)| k == nome).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Avvia il server su 127.0.0.1:`porta` e serve le richieste finché il processo non viene terminato
+pub fn avvia_server(porta: u16, collegamento: &LinkModel) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", porta))?;
+    println!(
+        "Server ETSI GS QKD 014 in ascolto su http://127.0.0.1:{}/api/v1/keys/ (SAE master {}, SAE slave {})",
+        porta,
+        nodo_scrittore(),
+        nodo_lettore()
+    );
+    for connessione in listener.incoming() {
+        let mut stream = match connessione {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Connessione non accettata: {}", e);
+                continue;
+            }
+        };
+        let (stato, corpo) = match leggi_richiesta(&mut stream) {
+            Ok(richiesta) => gestisci(&richiesta, collegamento),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => (413, json_errore(&e.to_string())),
+            Err(e) => (400, json_errore(&format!("richiesta non valida: {}", e))),
+        };
+        if let Err(e) = rispondi(&mut stream, stato, &corpo) {
+            eprintln!("Risposta non inviata: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn leggi_richiesta(stream: &mut TcpStream) -> io::Result<Richiesta> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut lettore = BufReader::new(stream);
+    let mut riga = String::new();
+    lettore.read_line(&mut riga)?;
+    let mut parti = riga.split_whitespace();
+    let (Some(metodo), Some(destinazione)) = (parti.next(), parti.next()) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "riga di richiesta malformata"));
+    };
+    let metodo = metodo.to_string();
+    let (percorso, query) = match destinazione.split_once('?') {
+        Some((p, q)) => (p.to_string(), leggi_query(q)),
+        None => (destinazione.to_string(), Vec::new()),
+    };
+
+    let mut lunghezza_corpo = 0;
+    loop {
+        let mut intestazione = String::new();
+        if lettore.read_line(&mut intestazione)? == 0 || intestazione.trim().is_empty() {
+            break;
+        }
+        if let Some((nome, valore)) = intestazione.split_once(':') {
+            if nome.trim().eq_ignore_ascii_case("content-length") {
+                lunghezza_corpo = valore
+                    .trim()
+                    .parse()
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Content-Length non valido"))?;
+            }
+        }
+    }
+    if lunghezza_corpo > MAX_CORPO_RICHIESTA {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("corpo di {} byte oltre il massimo di {}", lunghezza_corpo, MAX_CORPO_RICHIESTA),
+        ));
+    }
+    let mut corpo = vec![0u8; lunghezza_corpo];
+    lettore.read_exact(&mut corpo)?;
+    Ok(Richiesta { metodo, percorso, query, corpo: String::from_utf8_lossy(&corpo).into_owned() })
+}
+
+fn leggi_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter_map(|coppia| coppia.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn rispondi(stream: &mut TcpStream, stato: u16, corpo: &str) -> io::Result<()> {
+    let motivo = match stato {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "Service Unavailable",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        stato,
+        motivo,
+        corpo.len(),
+        corpo
+    )?;
+    stream.flush()
+}
+
+/// Instradamento: ritorna codice di stato HTTP e corpo JSON
+fn gestisci(r: &Richiesta, collegamento: &LinkModel) -> (u16, String) {
+    let segmenti: Vec<&str> = r.percorso.trim_matches('/').split('/').collect();
+    let ["api", "v1", "keys", sae, azione] = segmenti.as_slice() else {
+        return (404, json_errore("percorso sconosciuto"));
+    };
+    let metodo_ammesso = match *azione {
+        "status" => r.metodo == "GET",
+        "enc_keys" | "dec_keys" => r.metodo == "GET" || r.metodo == "POST",
+        _ => return (404, json_errore("percorso sconosciuto")),
+    };
+    if !metodo_ammesso {
+        return (405, json_errore("metodo non ammesso"));
+    }
+    // status ed enc_keys sono chiamati dal master indicando lo slave, dec_keys dallo slave indicando il master
+    let sae_atteso = if *azione == "dec_keys" { nodo_scrittore() } else { nodo_lettore() };
+    if *sae != sae_atteso {
+        return (401, json_errore(&format!("SAE sconosciuto: {}", sae)));
+    }
+    let esito = match *azione {
+        "status" => stato(),
+        "enc_keys" => chiavi_cifratura(r, collegamento),
+        _ => chiavi_decifratura(r),
+    };
+    esito.unwrap_or_else(|e| (503, json_errore(&format!("archivio chiavi: {}", e))))
+}
+
+fn stato() -> io::Result<(u16, String)> {
+    let disponibili = ArchivioChiavi::scrittore_da_config()?.bit_disponibili();
+    let corpo = format!(
+        "{{\"source_KME_ID\":\"KME_{}\",\"target_KME_ID\":\"KME_{}\",\"master_SAE_ID\":\"{}\",\"slave_SAE_ID\":\"{}\",\
+         \"key_size\":{},\"stored_key_count\":{},\"max_key_count\":{},\"max_key_per_request\":{},\
+         \"max_key_size\":{},\"min_key_size\":{},\"max_SAE_ID_count\":0}}",
+        nodo_scrittore(),
+        nodo_lettore(),
+        nodo_scrittore(),
+        nodo_lettore(),
+        DIM_CHIAVE_DEFAULT,
+        disponibili / DIM_CHIAVE_DEFAULT,
+        MAX_CHIAVI_ARCHIVIO,
+        MAX_CHIAVI_RICHIESTA,
+        DIM_CHIAVE_MAX,
+        DIM_CHIAVE_MIN
+    );
+    Ok((200, corpo))
+}
+
+/// enc_keys: preleva `number` chiavi di `size` bit dagli archivi di scrittore e lettore; la copia del
+/// lettore resta in consegna con lo stesso identificativo fino alla richiesta dec_keys.
+/// Tutte o nessuna: i bit sono controllati ed estratti in memoria e consumati con un solo salvataggio.
+fn chiavi_cifratura(r: &Richiesta, collegamento: &LinkModel) -> io::Result<(u16, String)> {
+    let numero = r.parametro("number").and_then(|v| v.parse().ok()).or_else(|| numero_json(&r.corpo, "number")).unwrap_or(1);
+    let dimensione = r
+        .parametro("size")
+        .and_then(|v| v.parse().ok())
+        .or_else(|| numero_json(&r.corpo, "size"))
+        .unwrap_or(DIM_CHIAVE_DEFAULT);
+    if numero == 0 || numero > MAX_CHIAVI_RICHIESTA {
+        return Ok((400, json_errore(&format!("number deve essere tra 1 e {}", MAX_CHIAVI_RICHIESTA))));
+    }
+    if !(DIM_CHIAVE_MIN..=DIM_CHIAVE_MAX).contains(&dimensione) || !dimensione.is_multiple_of(8) {
+        return Ok((400, json_errore(&format!("size deve essere un multiplo di 8 tra {} e {}", DIM_CHIAVE_MIN, DIM_CHIAVE_MAX))));
+    }
+
+    let richiesti = numero * dimensione;
+    let disponibili = ArchivioChiavi::scrittore_da_config()?.bit_disponibili();
+    if disponibili < richiesti {
+        // Ricarica: sessioni del simulatore fino ai bit mancanti
+        let risultato = esegui_chiave_obiettivo(collegamento, richiesti - disponibili, ROUND_MAX_RICARICA);
+        if !risultato.completata() {
+            return Ok((503, json_errore("generazione delle chiavi non riuscita (avversario rilevato o round esauriti)")));
+        }
+        archivia_chiave(&risultato.chiave_scrittore, &risultato.chiave_lettore, risultato.qber())?;
+    }
+    let mut consegna = ConsegnaChiavi::apri()?;
+    if consegna.bit_disponibili() < richiesti {
+        return Ok((503, json_errore("bit insufficienti")));
+    }
+    let mut chiavi = Vec::with_capacity(numero);
+    for _ in 0..numero {
+        let Some(voce) = consegna.estrai(dimensione) else {
+            return Ok((503, json_errore("bit insufficienti")));
+        };
+        chiavi.push((voce.id, voce.bit));
+    }
+    consegna.conferma()?;
+    Ok((200, json_chiavi(&chiavi)))
+}
+
+/// dec_keys: consegna (e cancella) le copie del lettore delle chiavi indicate; tutte o nessuna
+fn chiavi_decifratura(r: &Richiesta) -> io::Result<(u16, String)> {
+    let mut identificativi: Vec<String> = r.parametro("key_ID").map(|v| vec![v.to_string()]).unwrap_or_default();
+    identificativi.extend(stringhe_json(&r.corpo, "key_ID"));
+    // Un identificativo ripetuto vale una sola chiave: la consegna resta tutte o nessuna
+    let mut visti = HashSet::new();
+    identificativi.retain(|id| visti.insert(id.clone()));
+    if identificativi.is_empty() {
+        return Ok((400, json_errore("key_ID mancante")));
+    }
+    let mut consegne = ArchivioChiavi::consegne_lettore_da_config()?;
+    if let Some(sconosciuto) = identificativi.iter().find(|id| !consegne.voci().iter().any(|v| &v.id == *id)) {
+        return Ok((400, json_errore(&format!("key_ID sconosciuto o già consegnato: {}", sconosciuto))));
+    }
+    let mut chiavi = Vec::with_capacity(identificativi.len());
+    for id in identificativi {
+        if let Some(voce) = consegne.preleva_chiave(&id)? {
+            chiavi.push((id, voce.bit));
+        }
+    }
+    Ok((200, json_chiavi(&chiavi)))
+}
+
+fn json_chiavi(chiavi: &[(String, Vec<u8>)]) -> String {
+    let voci: Vec<String> = chiavi
+        .iter()
+        .map(|(id, bit)| format!("{{\"key_ID\":\"{}\",\"key\":\"{}\"}}", id, base64(&impacchetta_bit(bit))))
+        .collect();
+    format!("{{\"keys\":[{}]}}", voci.join(","))
+}
+
+fn json_errore(messaggio: &str) -> String {
+    format!("{{\"message\":\"{}\"}}", messaggio.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Valore numerico del campo `"campo": n` nel corpo JSON (lettura essenziale, senza annidamento)
+fn numero_json(corpo: &str, campo: &str) -> Option<usize> {
+    let chiave = format!("\"{}\"", campo);
+    let resto = corpo[corpo.find(&chiave)? + chiave.len()..].trim_start().strip_prefix(':')?.trim_start();
+    let cifre: String = resto.chars().take_while(|c| c.is_ascii_digit()).collect();
+    cifre.parse().ok()
+}
+
+/// Tutti i valori stringa del campo `"campo": "..."` nel corpo JSON
+fn stringhe_json(corpo: &str, campo: &str) -> Vec<String> {
+    let chiave = format!("\"{}\"", campo);
+    corpo
+        .match_indices(&chiave)
+        .filter_map(|(i, _)| {
+            let resto = corpo[i + chiave.len()..].trim_start().strip_prefix(':')?.trim_start().strip_prefix('"')?;
+            Some(resto[..resto.find('"')?].to_string())
+        })
+        .collect()
+}
+
+fn base64(byte: &[u8]) -> String {
+    const ALFABETO: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut uscita = String::with_capacity(byte.len().div_ceil(3) * 4);
+    for gruppo in byte.chunks(3) {
+        let n = (gruppo[0] as u32) << 16 | (*gruppo.get(1).unwrap_or(&0) as u32) << 8 | *gruppo.get(2).unwrap_or(&0) as u32;
+        for k in 0..4 {
+            if k <= gruppo.len() {
+                uscita.push(ALFABETO[(n >> (18 - 6 * k) & 0x3f) as usize] as char);
+            } else {
+                uscita.push('=');
+            }
+        }
+    }
+    uscita
+}
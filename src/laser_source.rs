@@ -0,0 +1,50 @@
+use rand::Rng;
+use crate::session_rng::generatore;
+
+/// Sorgente laser attenuata (impulsi coerenti deboli)
+/// Ogni impulso contiene un numero di fotoni distribuito secondo Poisson con media μ:
+/// P(n) = e^(-μ) μ^n / n!. Gli impulsi vuoti (n = 0) non possono essere rilevati dal lettore,
+/// quelli con n >= 2 espongono il protocollo agli attacchi multi-fotone.
+pub struct LaserSource {
+    pub mu: f64,
+}
+
+impl LaserSource {
+    pub fn new(mu: f64) -> Self {
+        assert!(mu >= 0.0);
+        Self { mu }
+    }
+
+    /// Estrae il numero di fotoni di un impulso con intensità μ
+    pub fn numero_fotoni_impulso(&self) -> u32 {
+        self.numero_fotoni_intensita(self.mu)
+    }
+
+    /// Estrae il numero di fotoni di un impulso con intensità media data (decoy-state),
+    /// con l'algoritmo di Knuth per la distribuzione di Poisson
+    pub fn numero_fotoni_intensita(&self, intensita: f64) -> u32 {
+        let mut rng = generatore();
+        let soglia = (-intensita).exp();
+        let mut n = 0u32;
+        let mut prodotto: f64 = rng.gen();
+        while prodotto > soglia {
+            n += 1;
+            prodotto *= rng.gen::<f64>();
+        }
+        n
+    }
+
+    /// Probabilità teorica di emettere esattamente `n` fotoni in un impulso
+    pub fn probabilita_fotoni(&self, n: u32) -> f64 {
+        self.probabilita_fotoni_intensita(n, self.mu)
+    }
+
+    /// Probabilità teorica di `n` fotoni in un impulso di intensità media data (decoy-state)
+    pub fn probabilita_fotoni_intensita(&self, n: u32, intensita: f64) -> f64 {
+        let mut p = (-intensita).exp();
+        for k in 1..=n {
+            p *= intensita / k as f64;
+        }
+        p
+    }
+}
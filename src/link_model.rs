@@ -0,0 +1,84 @@
+use rand::Rng;
+use crate::config::{
+    attenuazione_db_km, attiva_rivelatore, diametro_ricevitore_m, distanza_km, divergenza_urad, efficienza_rivelatore,
+    perdite_inserzione_db, tipo_collegamento, TipoCollegamento, DIAMETRO_TRASMETTITORE_M,
+};
+use crate::quantum_channel::QuantumChannel;
+use crate::session_rng::generatore;
+
+/// Modello del collegamento tra scrittore e lettore (fibra o spazio libero)
+/// Ogni fotone dell'impulso arriva al rivelatore del lettore con probabilità η (trasmittanza):
+/// η = 10^(-(α·L + perdite di inserzione + perdite geometriche) / 10) · η_rivelatore
+/// I fotoni persi riducono `numero_fotoni` del canale prima della lettura; un impulso che arriva
+/// vuoto è una mancata rilevazione per il lettore.
+#[derive(Debug, Clone)]
+pub struct LinkModel {
+    pub tipo: TipoCollegamento,
+    pub distanza_km: f64,
+    pub attenuazione_db_km: f64,
+    pub perdite_inserzione_db: f64,
+    pub efficienza_rivelatore: f64,
+    // Solo spazio libero: apertura del ricevitore e divergenza del fascio
+    pub diametro_ricevitore_m: f64,
+    pub divergenza_urad: f64,
+}
+
+impl LinkModel {
+    /// Collegamento configurato tramite costanti e variabili d'ambiente
+    pub fn da_config() -> Self {
+        Self {
+            tipo: tipo_collegamento(),
+            distanza_km: distanza_km(),
+            attenuazione_db_km: attenuazione_db_km(),
+            perdite_inserzione_db: perdite_inserzione_db(),
+            efficienza_rivelatore: efficienza_rivelatore(),
+            diametro_ricevitore_m: diametro_ricevitore_m(),
+            divergenza_urad: divergenza_urad(),
+        }
+    }
+
+    /// Stesso collegamento a una distanza diversa (per le curve tasso di chiave / distanza)
+    pub fn con_distanza(&self, distanza_km: f64) -> Self {
+        Self { distanza_km, ..self.clone() }
+    }
+
+    /// Perdite geometriche in spazio libero: il fascio si allarga con la distanza e il ricevitore
+    /// ne raccoglie solo la frazione (D_ricevitore / D_fascio)^2. In fibra sono nulle.
+    pub fn perdite_geometriche_db(&self) -> f64 {
+        match self.tipo {
+            TipoCollegamento::Fibra => 0.0,
+            TipoCollegamento::SpazioLibero => {
+                let diametro_fascio = DIAMETRO_TRASMETTITORE_M + self.distanza_km * 1000.0 * self.divergenza_urad * 1e-6;
+                let frazione = (self.diametro_ricevitore_m / diametro_fascio).powi(2).min(1.0);
+                -10.0 * frazione.log10()
+            }
+        }
+    }
+
+    /// Perdite totali del canale in dB (attenuazione, inserzione e geometriche; rivelatore escluso)
+    pub fn perdite_canale_db(&self) -> f64 {
+        self.attenuazione_db_km * self.distanza_km + self.perdite_inserzione_db + self.perdite_geometriche_db()
+    }
+
+    /// Trasmittanza del solo canale (fino all'ingresso del rivelatore)
+    pub fn trasmittanza_canale(&self) -> f64 {
+        10f64.powf(-self.perdite_canale_db() / 10.0)
+    }
+
+    /// Trasmittanza complessiva η per singolo fotone, efficienza del rivelatore inclusa
+    pub fn trasmittanza(&self) -> f64 {
+        self.trasmittanza_canale() * self.efficienza_rivelatore
+    }
+
+    /// Applica le perdite all'impulso nel canale: ogni fotone sopravvive con probabilità η.
+    /// Con il modello di rivelatore attivo l'efficienza è applicata dal `Detector` del lettore.
+    /// La luce intensa dell'accecamento arriva comunque al rivelatore.
+    pub fn applica_perdite(&self, q: &mut QuantumChannel) {
+        let eta = if attiva_rivelatore() { self.trasmittanza_canale() } else { self.trasmittanza() };
+        if eta >= 1.0 || q.luce_accecante {
+            return;
+        }
+        let mut rng = generatore();
+        q.numero_fotoni = (0..q.numero_fotoni).filter(|_| rng.gen_bool(eta)).count() as u32;
+    }
+}
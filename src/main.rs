@@ -1,88 +1,210 @@
+/// Stampa un messaggio di avanzamento del protocollo; soppresso nelle esecuzioni batch
+macro_rules! traccia {
+    ($($arg:tt)*) => {
+        if $crate::config::verboso() {
+            println!($($arg)*);
+        }
+    };
+}
+
 mod config;
+mod bit_vector;
 mod public_channel;
 mod quantum_channel;
 mod writer;
 mod reader;
 mod adversary;
+mod entangled_source;
+mod laser_source;
+mod decoy;
+mod information;
+mod privacy_amplification;
+mod link_model;
+mod detector;
+mod session;
+mod batch;
+mod finite_key;
+mod key_rate;
+mod trojan_horse;
+mod test_sampling;
+mod reconciliation;
+mod key_verification;
+mod target_key;
+mod key_store;
+mod kme_server;
+mod encryption;
+mod fast_engine;
+mod session_rng;
 
-use crate::config::{lung_msg, attiva_avversario};
-use public_channel::PublicChannel;
-use quantum_channel::QuantumChannel;
+use crate::config::{
+    attiva_archivio_chiavi, basi_simmetriche, motore_batch, porta_kme, protocollo, MotoreBatch, Protocollo, FATTORE_VERIFICA_EFFICIENZA,
+};
 use writer::Writer;
 use reader::Reader;
 use adversary::Adversary;
+use laser_source::LaserSource;
+use decoy::{ClasseIntensita, StatisticheDecoy, StimaDecoy};
+use finite_key::StimaChiaveFinita;
+use link_model::LinkModel;
+use detector::Detector;
+use public_channel::PublicChannel;
+use session::esegui_sessione;
+use batch::{esegui_batch, esegui_sweep};
+use key_rate::confronta_con_batch;
+use reconciliation::{confronta_riconciliatori, EsitoRiconciliazione};
+use key_verification::TagVerifica;
+use target_key::esegui_chiave_obiettivo;
+use key_store::{archivia_chiave, archivia_sessione, comando_archivio};
+use kme_server::avvia_server;
+use encryption::comando_cifratura;
+use fast_engine::{configurazione_non_supportata, confronta_motori};
+use session_rng::imposta_seme_sessione;
+use std::path::Path;
 
 fn main() {
-    // Inizializza canali e attori
-    let mut canale_pubblico = PublicChannel::new();
-    let mut canale_quantistico = QuantumChannel::new();
-    let mut scrittore = Writer::new();
-    let mut lettore = Reader::new();
-    let mut avversario = Adversary::new();
-
-    // 1) Scrittore inizializza il messaggio
-    scrittore.inizializzazione();
-
-    // 2) Trasmissione sequenziale di LUNG_MSG fotoni sul canale quantistico
-    for i in 0..lung_msg() {
-        // Scrittore spedisce il fotone i e attiva Fotone_IN
-        scrittore.scrivi_su_canale_quantistico(&mut canale_quantistico, i);
-
-        // Avversario (se attivo) legge per primo
-        avversario.leggi_fotone_se_attivo(&mut canale_quantistico);
-
-        // Lettore legge e resetta Fotone_IN
-        // (nel nostro Reader, il reset avviene dentro la chiamata di lettura iterativa)
-        // Per rispettare la specifica "un fotone alla volta" leggiamo esplicitamente qui.
-        {
-            use rand::Rng;
-            use crate::config::{POL_X, POL_Z};
-            let mut rng = rand::thread_rng();
-            let pol_let = if rng.gen_bool(0.5) { POL_Z } else { POL_X };
-            let val = canale_quantistico.lettura_fotone(pol_let);
-            lettore.messaggio_quantistico_ricevuto.push((pol_let, val));
-            canale_quantistico.set_fotone_out();
-        }
-    }
-
-    // 3) Segnalazioni post-lettura
-    if attiva_avversario() {
-        println!("[Avversario]: Lettura completata");
-    }
-
-    // Il lettore segnala fine lettura
-    lettore.segnala_fine_lettura(&mut canale_pubblico);
-    println!("[Lettore]: Lettura completata");
+    let argomenti: Vec<String> = std::env::args().skip(1).collect();
+    let collegamento = LinkModel::da_config();
+    segnala_accecamento_senza_rivelatore();
 
-    // 4) Lo scrittore pubblica le polarizzazioni utilizzate
-    scrittore.pubblicazione_polarizzazione(&mut canale_pubblico);
-
-    // 5) Il lettore attende pubblicazione e poi legge polarizzazioni,
-    //    calcola esito letture e lo invia
-    let polarizzazioni_scrittore = canale_pubblico.leggi_polarizzazioni();
-    lettore.elabora_e_invia_esito(&mut canale_pubblico, polarizzazioni_scrittore);
-
-    // 6) Lo scrittore attende che la sequenza sia pronta, seleziona chiave grezza
-    if canale_pubblico.sequenza_polarizzazioni_pronta {
-        scrittore.selezione_chiave_grezza(&mut canale_pubblico);
+    match argomenti.first().map(|s| s.as_str()) {
+        // Esecuzione di più sessioni con statistiche aggregate
+        Some("batch") => {
+            segnala_motore_non_supportato();
+            let sessioni = argomento_numerico(&argomenti, 1, 100.0) as usize;
+            esegui_batch(&collegamento, sessioni).stampa();
+        }
+        // Curva tasso di chiave / distanza: un batch per ogni distanza
+        Some("sweep") => {
+            let distanza_max = argomento_numerico(&argomenti, 1, 100.0);
+            let passo = argomento_numerico(&argomenti, 2, 10.0);
+            let sessioni = argomento_numerico(&argomenti, 3, 10.0) as usize;
+            let checkpoint = argomenti.get(4).map(Path::new);
+            let argomenti_validi = passo > 0.0 && passo.is_finite() && distanza_max >= 0.0 && distanza_max.is_finite();
+            if !argomenti_validi {
+                eprintln!("Sweep: il passo deve essere positivo e la distanza massima non negativa (km_max {}, passo_km {})", distanza_max, passo);
+                std::process::exit(2);
+            }
+            segnala_motore_non_supportato();
+            if let Err(e) = esegui_sweep(&collegamento, distanza_max, passo, sessioni, checkpoint) {
+                eprintln!("Checkpoint dello sweep: {}", e);
+                std::process::exit(1);
+            }
+        }
+        // Tasso di chiave asintotico (Shor-Preskill, GLLP) confrontato con un batch simulato
+        Some("keyrate") => {
+            let sessioni = argomento_numerico(&argomenti, 1, 100.0) as usize;
+            let qber = argomenti.get(2).and_then(|s| s.parse::<f64>().ok());
+            confronta_con_batch(&collegamento, sessioni, qber);
+        }
+        // Confronto degli schemi di riconciliazione su chiavi sintetiche
+        Some("reconcile") => {
+            let bit = argomento_numerico(&argomenti, 1, 10000.0) as usize;
+            let qber = argomento_numerico(&argomenti, 2, 0.03).clamp(0.0, 0.5);
+            let prove = argomento_numerico(&argomenti, 3, 10.0) as usize;
+            confronta_riconciliatori(bit, qber, prove);
+        }
+        // Confronto del motore a oggetti con quello vettoriale sulla configurazione corrente
+        Some("engines") => {
+            let sessioni = argomento_numerico(&argomenti, 1, 20.0) as usize;
+            confronta_motori(&collegamento, sessioni);
+        }
+        // Chiave di lunghezza obiettivo con tutti i round di trasmissione necessari
+        Some("target") => {
+            let bit = argomento_numerico(&argomenti, 1, 256.0) as usize;
+            let round_max = argomento_numerico(&argomenti, 2, 10000.0) as usize;
+            let risultato = esegui_chiave_obiettivo(&collegamento, bit, round_max);
+            risultato.stampa();
+            if attiva_archivio_chiavi() && risultato.completata() {
+                segnala_archiviazione(archivia_chiave(&risultato.chiave_scrittore, &risultato.chiave_lettore, risultato.qber()).map(Some));
+            }
+        }
+        // Consultazione e prelievo (monouso) delle chiavi archiviate
+        Some("keystore") => {
+            if let Err(e) = comando_archivio(&argomenti[1..]) {
+                eprintln!("Archivio chiavi: {}", e);
+                std::process::exit(1);
+            }
+        }
+        // Server REST di consegna chiavi (ETSI GS QKD 014) su localhost
+        Some("serve") => {
+            let porta = argomenti.get(1).and_then(|s| s.parse::<u16>().ok()).unwrap_or_else(porta_kme);
+            if let Err(e) = avvia_server(porta, &collegamento) {
+                eprintln!("Server di consegna chiavi: {}", e);
+                std::process::exit(1);
+            }
+        }
+        // Cifratura e decifratura di file o messaggi con le chiavi archiviate (OTP o AEAD)
+        Some(comando @ ("encrypt" | "decrypt")) => {
+            if let Err(e) = comando_cifratura(&argomenti[1..], comando == "encrypt") {
+                eprintln!("Cifratura: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(altro) => {
+            eprintln!(
+                "Comando sconosciuto: {} (comandi disponibili: batch [sessioni], sweep [km_max] [passo_km] [sessioni] [checkpoint], keyrate [sessioni] [qber], reconcile [bit] [qber] [prove], engines [sessioni], target [bit] [round_max], keystore [elenco|preleva|preleva_id], serve [porta], encrypt <otp|aead> [ingresso] [uscita], decrypt [ingresso] [uscita])",
+                altro
+            );
+            std::process::exit(2);
+        }
+        // Sessione singola con stampa delle tabelle richieste
+        None => {
+            // Con `SEME` configurato la sessione singola è ripetibile (è la sessione di indice 0 di un batch)
+            imposta_seme_sessione(0);
+            let sessione = esegui_sessione(&collegamento);
+            stampa_tabelle(&sessione.scrittore, &sessione.lettore, &sessione.avversario, &sessione.canale_pubblico);
+            if let Some(esito) = &sessione.riconciliazione {
+                stampa_statistiche_riconciliazione(esito);
+            }
+            if let Some(tag) = &sessione.canale_pubblico.tag_verifica {
+                stampa_verifica_chiave(tag, sessione.lettore.chiave_verificata);
+            }
+            if sessione.collegamento.trasmittanza() < 1.0 {
+                stampa_statistiche_collegamento(&sessione.collegamento, &sessione.lettore);
+            }
+            if attiva_archivio_chiavi() {
+                segnala_archiviazione(archivia_sessione(&sessione));
+            }
+        }
     }
+}
 
-    // 7) Il lettore invia i bit di test avversario sul canale pubblico
-    lettore.invia_test_avversario(&mut canale_pubblico);
+/// Esito dell'archiviazione di una chiave: identificativo assegnato o errore di scrittura dell'archivio
+fn segnala_archiviazione(esito: std::io::Result<Option<String>>) {
+    match esito {
+        Ok(Some(id)) => println!("
+Chiave archiviata con id {}", id),
+        Ok(None) => println!("
+Nessuna chiave da archiviare"),
+        Err(e) => eprintln!("Archivio chiavi: {}", e),
+    }
+}
 
-    // 8) Lo scrittore verifica il test e, se negativo, definisce la chiave finale,
-    //    quindi termina il processo
-    scrittore.selezione_test_e_chiave_finale(&mut canale_pubblico);
+/// Legge l'argomento numerico in posizione `indice`, altrimenti ritorna il default
+fn argomento_numerico(argomenti: &[String], indice: usize, default: f64) -> f64 {
+    argomenti.get(indice).and_then(|s| s.parse::<f64>().ok()).unwrap_or(default)
+}
 
-    // 9) Il lettore attende il termine del processo e conferma l'esito
-    lettore.conferma_finale(&canale_pubblico);
+/// Avvisa se è stato chiesto l'accecamento senza il modello di rivelatore del lettore: i rivelatori ideali
+/// non possono essere accecati e lo stato forzato viene misurato come un fotone (intercetta e rispedisci)
+fn segnala_accecamento_senza_rivelatore() {
+    use crate::config::{attiva_avversario, attiva_rivelatore, strategia_avversario, StrategiaAvversario};
+    if attiva_avversario() && strategia_avversario() == StrategiaAvversario::Accecamento && !attiva_rivelatore() {
+        eprintln!("Accecamento senza ATTIVA_RIVELATORE=true: il lettore ha rivelatori ideali, l'attacco equivale a intercetta e rispedisci");
+    }
+}
 
-    // 10) Stampa delle tabelle richieste
-    stampa_tabelle(&scrittore, &lettore, &avversario);
+/// Avvisa se è stato chiesto il motore vettoriale per una configurazione che non supporta
+fn segnala_motore_non_supportato() {
+    if motore_batch() == MotoreBatch::Vettoriale {
+        if let Some(motivo) = configurazione_non_supportata() {
+            eprintln!("Motore vettoriale non disponibile ({}): uso il motore a oggetti", motivo);
+        }
+    }
 }
 
 /// Stampa tabellare: sequenza fotoni, chiavi finali, statistiche
-fn stampa_tabelle(scr: &Writer, lettr: &Reader, avv: &Adversary) {
+fn stampa_tabelle(scr: &Writer, lettr: &Reader, avv: &Adversary, p: &PublicChannel) {
     use crate::config::attiva_avversario;
 
     println!("
@@ -91,16 +213,17 @@ fn stampa_tabelle(scr: &Writer, lettr: &Reader, avv: &Adversary) {
     println!("{}", "-".repeat(50));
     for i in 0..scr.messaggio_quantistico.len() {
         let (pol_s, val_s) = scr.messaggio_quantistico[i];
-        let (pol_a, val_a) = if attiva_avversario() {
-            avv.avversario_messaggio_quantistico_ricevuto.get(i).cloned().unwrap_or((' ', 0))
-        } else { ('-', 0) };
         let (pol_l, val_l) = lettr.messaggio_quantistico_ricevuto[i];
         println!(
             "{:<6} | {:<10} | {:<10} | {:<10}",
             i,
             format!("({}, {})", pol_s, val_s),
-            if attiva_avversario() { format!("({}, {})", pol_a, val_a) } else { "-".to_string() },
-            format!("({}, {})", pol_l, val_l)
+            match avv.misura(i) {
+                _ if !attiva_avversario() => "-".to_string(),
+                Some((pol_a, val_a)) => format!("({}, {})", pol_a, val_a),
+                None => "nessuna".to_string(),
+            },
+            if !lettr.rilevazioni[i] { "nessuna".to_string() } else { format!("({}, {})", pol_l, val_l) }
         );
     }
 
@@ -112,9 +235,10 @@ fn stampa_tabelle(scr: &Writer, lettr: &Reader, avv: &Adversary) {
     println!("
 === Statistiche ===");
     let tot_fotoni = scr.messaggio_quantistico.len();
-    let scartati_diff_polarizzazioni = lettr.esito_letture.iter().filter(|&&b| !b).count();
-    let selezionati_iniziali = lettr.esito_letture.iter().filter(|&&b| b).count();
-    let test_bits = if !lettr.chiave_grezza.is_empty() { ((lettr.chiave_grezza.len() - 1) / 8) + 1 } else { 0 };
+    let persi = lettr.rilevazioni.len() - lettr.rilevazioni.conta_uni();
+    let scartati_diff_polarizzazioni = lettr.rilevazioni.and(&lettr.esito_letture.negato()).conta_uni();
+    let selezionati_iniziali = lettr.esito_letture.conta_uni();
+    let test_bits = lettr.test_avversario.len();
     let lung_chiave_finale = scr.chiave_simmetrica.len();
     
     // Calcola valori identici quando polarizzazione è uguale
@@ -132,9 +256,295 @@ fn stampa_tabelle(scr: &Writer, lettr: &Reader, avv: &Adversary) {
     fn perc(x: usize, tot: usize) -> f64 { if tot == 0 { 0.0 } else { (x as f64) * 100.0 / (tot as f64) } }
 
     println!("Fotoni totali (iniziali)              : {:>3} ({:>5.1}%)", tot_fotoni, perc(tot_fotoni, tot_fotoni));
+    println!("Fotoni persi (nessuna rilevazione)    : {:>3} ({:>5.1}%)", persi, perc(persi, tot_fotoni));
     println!("Valori scartati per differenza pol.   : {:>3} ({:>5.1}%)", scartati_diff_polarizzazioni, perc(scartati_diff_polarizzazioni, tot_fotoni));
     println!("Valori selezionati inizialmente chiave: {:>3} ({:>5.1}%)", selezionati_iniziali, perc(selezionati_iniziali, tot_fotoni));
-    println!("% valori identici stessa polarizzaz.  : {:>3} ({:>5.1}%)", valori_identici_stessa_pol, perc(valori_identici_stessa_pol, selezionati_iniziali));
+    if protocollo() != Protocollo::SARG04 {
+        println!("% valori identici stessa polarizzaz.  : {:>3} ({:>5.1}%)", valori_identici_stessa_pol, perc(valori_identici_stessa_pol, selezionati_iniziali));
+    }
     println!("Valori scartati per test avversario   : {:>3} ({:>5.1}%)", test_bits, perc(test_bits, tot_fotoni));
     println!("Lunghezza chiave finale               : {:>3} ({:>5.1}%)", lung_chiave_finale, perc(lung_chiave_finale, tot_fotoni));
+
+    if protocollo() == Protocollo::SARG04 {
+        stampa_statistiche_sarg04(scr, lettr);
+    } else if !basi_simmetriche() {
+        stampa_statistiche_basi(scr, lettr);
+    }
+    if let Some(laser) = &scr.sorgente_laser {
+        stampa_statistiche_sorgente_laser(laser, scr, lettr);
+    }
+    if let Some(rivelatore) = &lettr.rivelatore {
+        stampa_statistiche_rivelatore("lettore", rivelatore, lettr.messaggio_quantistico_ricevuto.len());
+    }
+    if let Some(rivelatore) = &avv.rivelatore {
+        stampa_statistiche_rivelatore("avversario", rivelatore, avv.avversario_messaggio_quantistico_ricevuto.len());
+    }
+    if let (Some(statistiche), Some(stima)) = (&scr.statistiche_decoy, &scr.stima_decoy) {
+        stampa_statistiche_decoy(statistiche, stima);
+    }
+    if let Some(stima) = &scr.stima_chiave_finita {
+        stampa_statistiche_chiave_finita(stima);
+    }
+    if attiva_avversario() {
+        stampa_statistiche_avversario(scr, lettr, avv, p);
+    }
+}
+
+/// Statistiche con basi asimmetriche: per ciascuna base bit della chiave grezza, bit di test e QBER stimato
+fn stampa_statistiche_basi(scr: &Writer, lettr: &Reader) {
+    use crate::config::{base_maggioritaria, bit_da_base, prob_base_z, POL_X, POL_Z};
+
+    fn perc(x: usize, tot: usize) -> f64 { if tot == 0 { 0.0 } else { (x as f64) * 100.0 / (tot as f64) } }
+
+    println!("
+=== Basi asimmetriche (P(Z) = {:.3}, chiave in base {}) ===", prob_base_z(), base_maggioritaria());
+    println!("{:<4} | {:>10} | {:>9} | {:>7} | {:>7}", "Base", "Bit grezzi", "Bit test", "Errori", "QBER");
+    println!("{}", "-".repeat(48));
+    for base in [POL_Z, POL_X] {
+        let mut grezzi = 0;
+        let mut test = 0;
+        let mut errori = 0;
+        for (k, (b, in_test)) in scr.basi_chiave_grezza.iter().zip(scr.selezione_test.iter()).enumerate() {
+            if b != (bit_da_base(base) == 1) { continue; }
+            grezzi += 1;
+            if in_test {
+                test += 1;
+                if scr.chiave_grezza.get(k) != lettr.chiave_grezza.get(k) { errori += 1; }
+            }
+        }
+        println!("{:<4} | {:>10} | {:>9} | {:>7} | {:>6.2}%", base, grezzi, test, errori, perc(errori, test));
+    }
+}
+
+/// Statistiche del collegamento: perdite, trasmittanza e rilevazioni effettive del lettore
+fn stampa_statistiche_collegamento(c: &LinkModel, lettr: &Reader) {
+    let impulsi = lettr.messaggio_quantistico_ricevuto.len();
+    let rilevati = lettr.rilevazioni.conta_uni();
+
+    println!("
+=== Collegamento ({:?}, {:.1} km) ===", c.tipo, c.distanza_km);
+    println!("Perdite canale (dB)                   : {:>9.2}", c.perdite_canale_db());
+    println!("  di cui geometriche (dB)             : {:>9.2}", c.perdite_geometriche_db());
+    println!("Efficienza rivelatore                 : {:>9.3}", c.efficienza_rivelatore);
+    println!("Trasmittanza complessiva eta          : {:>9.3e}", c.trasmittanza());
+    println!("Impulsi rilevati dal lettore          : {:>3} su {} ({:.2}%)", rilevati, impulsi, if impulsi == 0 { 0.0 } else { rilevati as f64 * 100.0 / impulsi as f64 });
+}
+
+/// Statistiche del modello di rivelatore: click, conteggi di buio, afterpulse, doppi click e tempo morto
+fn stampa_statistiche_rivelatore(attore: &str, r: &Detector, slot: usize) {
+    fn perc(x: usize, tot: usize) -> f64 { if tot == 0 { 0.0 } else { (x as f64) * 100.0 / (tot as f64) } }
+
+    println!("
+=== Rivelatore {} (eff. {:.2}, buio {:.1e}, afterpulse {:.3}, tempo morto {} slot) ===",
+        attore, r.efficienza, r.prob_conteggio_buio, r.prob_afterpulse, r.tempo_morto_slot);
+    println!("Slot con click                        : {:>3} ({:>5.1}%)", r.click, perc(r.click, slot));
+    println!("Conteggi di buio                      : {:>3} ({:>5.1}%)", r.conteggi_buio, perc(r.conteggi_buio, slot));
+    println!("Afterpulse                            : {:>3} ({:>5.1}%)", r.afterpulse, perc(r.afterpulse, slot));
+    println!("Doppi click (valore casuale)          : {:>3} ({:>5.1}%)", r.doppi_click, perc(r.doppi_click, slot));
+    println!("Slot con rivelatore in tempo morto    : {:>3} ({:>5.1}%)", r.slot_tempo_morto, perc(r.slot_tempo_morto, slot));
+    if r.click_forzati > 0 {
+        println!("Click forzati (rivelatori accecati)   : {:>3} ({:>5.1}%)", r.click_forzati, perc(r.click_forzati, slot));
+    }
+    if r.fotodiodo_sorveglianza {
+        println!("Allarmi fotodiodo di sorveglianza     : {:>3} ({:>5.1}%)", r.allarmi_fotodiodo, perc(r.allarmi_fotodiodo, slot));
+    }
+    if r.prob_verifica_efficienza > 0.0 {
+        println!("Slot di verifica efficienza / click   : {:>3} / {}", r.slot_verifica, r.click_verifica);
+        match r.rapporto_verifica() {
+            Some(rapporto) => println!("Rapporto click verifica/normali       : {:>9.3} (atteso {:.3})", rapporto, FATTORE_VERIFICA_EFFICIENZA),
+            None => println!("Rapporto click verifica/normali       :       n/d"),
+        }
+    }
+    if r.fotodiodo_sorveglianza || r.prob_verifica_efficienza > 0.0 {
+        println!("Accecamento rilevato                  : {:>9}", if r.allarme_accecamento() { "sì" } else { "no" });
+    }
+}
+
+/// Statistiche decoy-state: guadagni ed errori per classe e parametri a singolo fotone stimati
+fn stampa_statistiche_decoy(statistiche: &StatisticheDecoy, stima: &StimaDecoy) {
+    println!("
+=== Stima decoy-state ===");
+    println!("{:<8} | {:>8} | {:>8} | {:>10} | {:>8}", "Classe", "Impulsi", "Rilevati", "Guadagno Q", "Errore E");
+    println!("{}", "-".repeat(54));
+    for classe in [ClasseIntensita::Segnale, ClasseIntensita::Esca, ClasseIntensita::Vuoto] {
+        let i = classe.indice();
+        println!(
+            "{:<8} | {:>8} | {:>8} | {:>10.4} | {:>8.4}",
+            format!("{:?}", classe), statistiche.impulsi[i], statistiche.rilevati[i],
+            statistiche.guadagno(classe), statistiche.qber(classe)
+        );
+    }
+    println!("Resa impulsi vuoti Y0                 : {:>9.4}", stima.y0);
+    println!("Resa singolo fotone Y1 (lim. inf.)    : {:>9.4}", stima.y1_l);
+    println!("Guadagno singolo fotone Q1 (lim. inf.): {:>9.4}", stima.q1_l);
+    println!("Errore singolo fotone e1 (lim. sup.)  : {:>9.4}", stima.e1_u);
+    println!("Lunghezza chiave sicura               : {:>5}", stima.lunghezza_chiave);
+}
+
+/// Verifica della chiave: lunghezza del tag, probabilità di collisione ed esito del confronto
+fn stampa_verifica_chiave(tag: &TagVerifica, superata: bool) {
+    println!("
+=== Verifica chiave (hash universale) ===");
+    println!("Bit del tag                           : {:>9}", tag.tag.len());
+    println!("Probabilità di collisione             : {:>9.2e}", tag.prob_collisione());
+    println!("Esito                                 : {:>9}", if superata { "superata" } else { "fallita" });
+}
+
+/// Analisi a chiave finita: campione di test, correzione statistica sul QBER e lunghezza sicura
+fn stampa_statistiche_chiave_finita(s: &StimaChiaveFinita) {
+    println!("
+=== Analisi a chiave finita (eps_sec {:.0e}, eps_cor {:.0e}) ===", s.epsilon_sec, s.epsilon_cor);
+    println!("Bit di chiave prima della compressione: {:>9}", s.bit_chiave);
+    println!("Bit di test / errati                  : {:>9} / {}", s.bit_test, s.errori_test);
+    println!("QBER osservato                        : {:>9.4}", s.qber_osservato);
+    println!("Correzione statistica delta           : {:>9.4}", s.deviazione);
+    println!("Perdita di riconciliazione (bit)      : {:>9.1}", s.perdita_riconciliazione);
+    println!("Lunghezza chiave sicura               : {:>9}", s.lunghezza_chiave);
+}
+
+/// Riconciliazione: bit pubblicati, efficienza rispetto al limite di Shannon, round ed errori residui
+fn stampa_statistiche_riconciliazione(e: &EsitoRiconciliazione) {
+    println!("
+=== Riconciliazione ({}) ===", e.metodo);
+    println!("Bit di chiave riconciliati            : {:>9}", e.bit_chiave);
+    println!("Errori prima della riconciliazione    : {:>9} (QBER {:.2}%)", e.errori_iniziali, e.qber() * 100.0);
+    println!("Bit rivelati (leak)                   : {:>9}", e.bit_rivelati);
+    match e.efficienza() {
+        Some(f) => println!("Efficienza f = leak / n h(QBER)       : {:>9.3}", f),
+        None => println!("Efficienza f = leak / n h(QBER)       : {:>9}", "n/d"),
+    }
+    println!("Round di comunicazione                : {:>9}", e.round);
+    println!("Errori residui                        : {:>9}", e.errori_residui);
+    if e.interrotta {
+        println!("Riconciliazione interrotta            : perdita oltre il limite, blocco scartato");
+    }
+}
+
+/// Statistiche dell'avversario: impulsi bloccati/trattenuti, conoscenza della chiave grezza e finale,
+/// informazione mutua I(A:E) confrontata con I(A:B) e bit di test che ne rivelano la presenza
+fn stampa_statistiche_avversario(scr: &Writer, lettr: &Reader, avv: &Adversary, p: &PublicChannel) {
+    use crate::config::{strategia_avversario, StrategiaAvversario};
+
+    // Con il PNS gli impulsi a singolo fotone sono bloccati: le sole misure sono sui fotoni separati
+    let trattenuti = avv.slot_misurati.conta_uni();
+    let tot_impulsi = scr.messaggio_quantistico.len();
+    let (noti, bit_chiave) = avv.bit_noti_chiave_finale(scr);
+    let bit_test = scr.test_avversario.len().min(lettr.test_avversario.len());
+    let errori_test = scr
+        .test_avversario
+        .iter()
+        .zip(lettr.test_avversario.iter())
+        .filter(|(a, b)| a != b)
+        .count();
+
+    fn perc(x: usize, tot: usize) -> f64 { if tot == 0 { 0.0 } else { (x as f64) * 100.0 / (tot as f64) } }
+
+    println!("
+=== Statistiche avversario ({:?}) ===", strategia_avversario());
+    println!("Impulsi attaccati                     : {:>3} ({:>5.1}%)", avv.impulsi_attaccati, perc(avv.impulsi_attaccati, tot_impulsi));
+    if strategia_avversario() == StrategiaAvversario::CavalloDiTroia {
+        use crate::config::attiva_monitor_scrittore;
+        use crate::trojan_horse::{fotoni_riflessi, prob_lettura_base, prob_rilevazione_monitor};
+        println!("Fotoni riflessi medi per sonda        : {:>9.3e}", fotoni_riflessi());
+        println!("Basi lette con la sonda               : {:>3} ({:>5.1}%, attesa {:.1}%)", avv.basi_sondate, perc(avv.basi_sondate, avv.impulsi_attaccati), prob_lettura_base() * 100.0);
+        if attiva_monitor_scrittore() {
+            println!("Sonde rilevate dal monitor scrittore  : {:>3} ({:>5.1}%, attesa {:.1}%)", scr.sonde_rilevate, perc(scr.sonde_rilevate, avv.impulsi_attaccati), prob_rilevazione_monitor() * 100.0);
+        }
+    }
+    if strategia_avversario() == StrategiaAvversario::Pns {
+        println!("Impulsi a singolo fotone bloccati     : {:>3} ({:>5.1}%)", avv.impulsi_bloccati, perc(avv.impulsi_bloccati, tot_impulsi));
+        println!("Fotoni multi-fotone misurati          : {:>3} ({:>5.1}%)", trattenuti, perc(trattenuti, tot_impulsi));
+    }
+    println!("Bit chiave finale noti all'avversario : {:>3} ({:>5.1}%)", noti, perc(noti, bit_chiave));
+    println!("Bit di test errati (rilevazione)      : {:>3} ({:>5.1}%)", errori_test, perc(errori_test, bit_test));
+
+    let c = avv.conoscenza_chiave(scr, lettr, p);
+    println!("Chiave grezza indovinata (avversario) : {:>3} ({:>5.1}%)", c.accordo_grezza_avversario, perc(c.accordo_grezza_avversario, c.bit_grezzi));
+    println!("Chiave grezza concorde (lettore)      : {:>3} ({:>5.1}%)", c.accordo_grezza_lettore, perc(c.accordo_grezza_lettore, c.bit_grezzi));
+    println!("Chiave senza test indovinata          : {:>3} ({:>5.1}%)", c.accordo_chiave_avversario, perc(c.accordo_chiave_avversario, c.bit_chiave));
+    println!("I(A:B) per bit (chiave grezza)        : {:>9.4}", c.informazione_ab());
+    println!("I(A:E) per bit (chiave grezza)        : {:>9.4}", c.informazione_ae());
+    if c.bit_finali == 0 {
+        println!("Chiave finale                         : nessuna (sessione senza chiave)");
+    } else {
+        println!(
+            "Chiave finale indovinata{}: {:>3} ({:>5.1}%)",
+            if c.amplificazione { " (dopo PA)    " } else { " (senza PA)   " },
+            c.accordo_finale_avversario,
+            perc(c.accordo_finale_avversario, c.bit_finali)
+        );
+        println!("I(A:E) per bit (chiave finale)        : {:>9.4}", c.informazione_ae_finale());
+    }
+
+    // La memoria permette di misurare nella base corretta, ma il sostituto inoltrato è casuale e
+    // raddoppia il disturbo rispetto all'intercetta-rispedisci: valori attesi a parità di frazione attaccata
+    if matches!(strategia_avversario(), StrategiaAvversario::IntercettaRispedisci | StrategiaAvversario::Memoria) {
+        let f = perc(avv.impulsi_attaccati, tot_impulsi) / 100.0;
+        println!("Atteso intercetta-rispedisci          : QBER {:>5.1}%, chiave indovinata {:>5.1}%", 25.0 * f, 50.0 + 25.0 * f);
+        println!("Atteso memoria quantistica            : QBER {:>5.1}%, chiave indovinata {:>5.1}%", 50.0 * f, 50.0 + 50.0 * f);
+    }
+}
+
+/// Statistiche della sorgente laser: composizione degli impulsi e tasso di rilevazione del lettore
+fn stampa_statistiche_sorgente_laser(laser: &LaserSource, scr: &Writer, lettr: &Reader) {
+    let tot_impulsi = scr.numero_fotoni_impulsi.len();
+    let vuoti = scr.numero_fotoni_impulsi.iter().filter(|&&n| n == 0).count();
+    let singoli = scr.numero_fotoni_impulsi.iter().filter(|&&n| n == 1).count();
+    let multi = tot_impulsi - vuoti - singoli;
+    let fotoni_emessi: u32 = scr.numero_fotoni_impulsi.iter().sum();
+    let rilevati = lettr.rilevazioni.conta_uni();
+    // Probabilità attese: con il decoy-state è la media sulle intensità effettivamente usate
+    let atteso = |n: u32| -> f64 {
+        if scr.classi_intensita.is_empty() {
+            laser.probabilita_fotoni(n)
+        } else {
+            let somma: f64 = scr.classi_intensita.iter().map(|c| laser.probabilita_fotoni_intensita(n, c.intensita())).sum();
+            somma / scr.classi_intensita.len() as f64
+        }
+    };
+    let p_multi = 1.0 - atteso(0) - atteso(1);
+
+    fn perc(x: usize, tot: usize) -> f64 { if tot == 0 { 0.0 } else { (x as f64) * 100.0 / (tot as f64) } }
+
+    println!("
+=== Statistiche sorgente laser (mu = {:.3}) ===", laser.mu);
+    println!("Fotoni medi per impulso (misurati)    : {:>9.3}", if tot_impulsi == 0 { 0.0 } else { fotoni_emessi as f64 / tot_impulsi as f64 });
+    println!("Impulsi vuoti                         : {:>3} ({:>5.1}%, atteso {:>5.1}%)", vuoti, perc(vuoti, tot_impulsi), atteso(0) * 100.0);
+    println!("Impulsi a singolo fotone              : {:>3} ({:>5.1}%, atteso {:>5.1}%)", singoli, perc(singoli, tot_impulsi), atteso(1) * 100.0);
+    println!("Impulsi multi-fotone                  : {:>3} ({:>5.1}%, atteso {:>5.1}%)", multi, perc(multi, tot_impulsi), p_multi * 100.0);
+    println!("Impulsi rilevati dal lettore          : {:>3} ({:>5.1}%)", rilevati, perc(rilevati, tot_impulsi));
+}
+
+/// Statistiche specifiche SARG04: esiti conclusivi/inconclusivi e bit inferiti errati
+fn stampa_statistiche_sarg04(scr: &Writer, lettr: &Reader) {
+    let tot_fotoni = scr.messaggio_quantistico.len();
+    let conclusivi = lettr.esito_letture.conta_uni();
+    let rilevati = lettr.rilevazioni.conta_uni();
+    let inconclusivi = rilevati - conclusivi;
+
+    // Esiti conclusivi ottenuti misurando nella stessa base dello stato spedito (possibili solo con errori/avversario)
+    let mut conclusivi_stessa_base = 0;
+    for i in 0..tot_fotoni {
+        if lettr.esito_letture[i] && scr.messaggio_quantistico[i].0 == lettr.messaggio_quantistico_ricevuto[i].0 {
+            conclusivi_stessa_base += 1;
+        }
+    }
+
+    // Bit della chiave grezza del lettore diversi da quelli dello scrittore
+    let errori_grezza = scr
+        .chiave_grezza
+        .iter()
+        .zip(lettr.chiave_grezza.iter())
+        .filter(|(a, b)| a != b)
+        .count();
+
+    fn perc(x: usize, tot: usize) -> f64 { if tot == 0 { 0.0 } else { (x as f64) * 100.0 / (tot as f64) } }
+
+    println!("
+=== Statistiche SARG04 ===");
+    println!("Esiti conclusivi                      : {:>3} ({:>5.1}%)", conclusivi, perc(conclusivi, tot_fotoni));
+    println!("Esiti inconclusivi (scartati)         : {:>3} ({:>5.1}%)", inconclusivi, perc(inconclusivi, tot_fotoni));
+    println!("Slot senza rilevazione                : {:>3} ({:>5.1}%)", tot_fotoni - rilevati, perc(tot_fotoni - rilevati, tot_fotoni));
+    println!("Conclusivi con stessa base di misura  : {:>3} ({:>5.1}%)", conclusivi_stessa_base, perc(conclusivi_stessa_base, conclusivi));
+    println!("Bit errati nella chiave grezza        : {:>3} ({:>5.1}%)", errori_grezza, perc(errori_grezza, conclusivi));
 }
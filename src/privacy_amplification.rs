@@ -0,0 +1,26 @@
+use rand::Rng;
+use crate::bit_vector::VettoreBit;
+use crate::session_rng::generatore;
+
+// Amplificazione della privacy con hashing universale (matrici di Toeplitz binarie)
+// La matrice m x n è definita da un seme pubblico di n + m - 1 bit: T[i][j] = seme[i - j + n - 1].
+// Scrittore e lettore applicano la stessa matrice alla chiave e ottengono m bit
+// sui quali l'informazione dell'avversario è ridotta in modo esponenziale.
+
+/// Genera il seme casuale (pubblico) per comprimere `lunghezza_ingresso` bit in `lunghezza_uscita` bit
+pub fn genera_seme(lunghezza_ingresso: usize, lunghezza_uscita: usize) -> VettoreBit {
+    let mut rng = generatore();
+    let lunghezza_seme = (lunghezza_ingresso + lunghezza_uscita).saturating_sub(1);
+    VettoreBit::da_parole((0..lunghezza_seme.div_ceil(64)).map(|_| rng.gen()).collect(), lunghezza_seme)
+}
+
+/// Comprime la chiave con la matrice di Toeplitz definita dal seme (prodotto matrice-vettore in GF(2)).
+/// Con la chiave rovesciata r[t] = chiave[n - 1 - t] la riga i è la finestra seme[i..i + n]:
+/// ogni bit di uscita è la parità di un AND calcolato per parole da 64 bit.
+pub fn comprimi_chiave(chiave: &VettoreBit, lunghezza_uscita: usize, seme: &VettoreBit) -> VettoreBit {
+    let n = chiave.len();
+    assert!(lunghezza_uscita <= n);
+    assert_eq!(seme.len(), (n + lunghezza_uscita).saturating_sub(1));
+    let rovesciata = chiave.rovesciato();
+    (0..lunghezza_uscita).map(|i| seme.parita_finestra(i, &rovesciata) == 1).collect()
+}
@@ -1,15 +1,37 @@
+use crate::bit_vector::VettoreBit;
 use crate::config::lung_msg;
+use crate::decoy::{ClasseIntensita, StatisticheDecoy};
+use crate::key_verification::TagVerifica;
 
 /// Canale Pubblico
 /// Gestisce vettori condivisi e variabili booleane come da specifica.
 #[derive(Debug, Clone)]
 pub struct PublicChannel {
-    // Vettore di polarizzazioni pubblicato dallo scrittore (Z/X) di lunghezza LUNG_MSG
-    pub canale_pubblico: Vec<char>,
-    // Vettore di esito letture (true se polarizzazione lettore == polarizzazione scrittore)
-    pub sequenza_ricezione: Vec<bool>,
+    // Vettore di polarizzazioni pubblicato dallo scrittore (Z -> 0, X -> 1) di lunghezza LUNG_MSG
+    pub canale_pubblico: VettoreBit,
+    // Vettore degli slot rilevati dal lettore (false: fotone perso o nessun click)
+    pub sequenza_rilevazioni: VettoreBit,
+    // Vettore di esito letture (true se slot rilevato e polarizzazione lettore == polarizzazione scrittore)
+    pub sequenza_ricezione: VettoreBit,
     // Valori di test avversario inviati dal lettore e letti dallo scrittore
-    pub test_avversario: Vec<u8>,
+    pub test_avversario: VettoreBit,
+    // SARG04: coppie di stati non ortogonali (valore in base Z, valore in base X) annunciate dallo scrittore
+    pub coppie_stati: Vec<(u8, u8)>,
+    // Decoy-state: classi di intensità annunciate dallo scrittore dopo la trasmissione
+    pub classi_intensita: Vec<ClasseIntensita>,
+    // Decoy-state: bit (esca e vuoto) rivelati dal lettore per la stima
+    pub bit_stima_decoy: Vec<u8>,
+    // Decoy-state: statistiche complete per classe pubblicate dallo scrittore
+    pub statistiche_decoy: StatisticheDecoy,
+    // Seme pubblico della matrice di Toeplitz per l'amplificazione della privacy
+    pub seme_amplificazione: VettoreBit,
+    // Errori trovati dallo scrittore nei bit di test (pubblicati dopo il confronto)
+    pub errori_test: usize,
+    // Riconciliazione: bit pubblicati per la correzione degli errori (None se non eseguita)
+    pub bit_riconciliazione: Option<usize>,
+    // Verifica della chiave: seme e tag di hash universale dello scrittore, esito del confronto del lettore
+    pub tag_verifica: Option<TagVerifica>,
+    pub chiave_verificata: Option<bool>,
 
     // Flag di stato
     pub pubblicazione_pronta: bool,
@@ -18,35 +40,133 @@ pub struct PublicChannel {
     pub test_avversario_pronto: bool,
     pub chiave_simmetrica_ok: bool,
     pub processo_terminato: bool,
+    pub allarme_rivelatore: bool,
+    pub avversario_rilevato: bool, // chiave scartata per test o allarmi (non per mancanza di bit sicuri o tag diversi)
+
+    // Numero di slot della sessione: lunghezza attesa delle sequenze pubblicate per slot
+    lunghezza: usize,
 }
 
 impl PublicChannel {
     /// Inizializzazione del canale pubblico
     pub fn new() -> Self {
+        Self::con_lunghezza(lung_msg())
+    }
+
+    /// Canale pubblico per una sessione di `lunghezza` slot (motore vettoriale con parametri espliciti)
+    pub fn con_lunghezza(lunghezza: usize) -> Self {
         Self {
-            canale_pubblico: vec![' '; lung_msg()],
-            sequenza_ricezione: vec![false; lung_msg()],
-            test_avversario: Vec::new(),
+            canale_pubblico: VettoreBit::zeri(lunghezza),
+            sequenza_rilevazioni: VettoreBit::zeri(lunghezza),
+            sequenza_ricezione: VettoreBit::zeri(lunghezza),
+            test_avversario: VettoreBit::new(),
+            coppie_stati: Vec::new(),
+            classi_intensita: Vec::new(),
+            bit_stima_decoy: Vec::new(),
+            statistiche_decoy: StatisticheDecoy::default(),
+            seme_amplificazione: VettoreBit::new(),
+            errori_test: 0,
+            bit_riconciliazione: None,
+            tag_verifica: None,
+            chiave_verificata: None,
             pubblicazione_pronta: false,
             fine_lettura: false,
             sequenza_polarizzazioni_pronta: false,
             test_avversario_pronto: false,
             chiave_simmetrica_ok: false,
             processo_terminato: false,
+            allarme_rivelatore: false,
+            avversario_rilevato: false,
+            lunghezza,
         }
     }
 
     /// Pubblicazione polarizzazione dei fotoni trasmessi (scrittore -> canale)
-    pub fn pubblica_polarizzazioni(&mut self, polarizzazioni: Vec<char>) {
-        assert_eq!(polarizzazioni.len(), lung_msg());
+    pub fn pubblica_polarizzazioni(&mut self, polarizzazioni: VettoreBit) {
+        assert_eq!(polarizzazioni.len(), self.lunghezza);
         self.canale_pubblico = polarizzazioni;
         self.pubblicazione_pronta = true;
     }
 
     /// Lettura polarizzazione dei fotoni trasmessi (lettore)
-    pub fn leggi_polarizzazioni(&mut self) -> Vec<char> {
+    pub fn leggi_polarizzazioni(&mut self) -> &VettoreBit {
+        self.pubblicazione_pronta = false;
+        &self.canale_pubblico
+    }
+
+    /// SARG04: pubblicazione delle coppie di stati non ortogonali (scrittore -> canale)
+    pub fn pubblica_coppie_stati(&mut self, coppie: Vec<(u8, u8)>) {
+        assert_eq!(coppie.len(), self.lunghezza);
+        self.coppie_stati = coppie;
+        self.pubblicazione_pronta = true;
+    }
+
+    /// SARG04: lettura delle coppie di stati annunciate (lettore)
+    pub fn leggi_coppie_stati(&mut self) -> &[(u8, u8)] {
         self.pubblicazione_pronta = false;
-        self.canale_pubblico.clone()
+        &self.coppie_stati
+    }
+
+    /// Decoy-state: pubblicazione delle classi di intensità degli impulsi (scrittore -> canale)
+    pub fn pubblica_classi_intensita(&mut self, classi: Vec<ClasseIntensita>) {
+        assert_eq!(classi.len(), self.lunghezza);
+        self.classi_intensita = classi;
+    }
+
+    /// Decoy-state: lettura delle classi di intensità (lettore)
+    pub fn leggi_classi_intensita(&self) -> &[ClasseIntensita] {
+        &self.classi_intensita
+    }
+
+    /// Decoy-state: bit esca/vuoto per la stima (lettore -> scrittore)
+    pub fn invia_dati_stima_decoy(&mut self, bit: Vec<u8>) {
+        self.bit_stima_decoy = bit;
+    }
+
+    /// Decoy-state: statistiche per classe complete di errori (scrittore -> lettore)
+    pub fn pubblica_statistiche_decoy(&mut self, statistiche: StatisticheDecoy) {
+        self.statistiche_decoy = statistiche;
+    }
+
+    /// Pubblicazione del seme per l'amplificazione della privacy (scrittore -> lettore)
+    pub fn pubblica_seme_amplificazione(&mut self, seme: VettoreBit) {
+        self.seme_amplificazione = seme;
+    }
+
+    /// Pubblicazione del numero di errori nei bit di test (scrittore -> lettore)
+    pub fn pubblica_errori_test(&mut self, errori: usize) {
+        self.errori_test = errori;
+    }
+
+    /// Riconciliazione conclusa: numero di bit pubblicati per la correzione degli errori
+    pub fn pubblica_riconciliazione(&mut self, bit_rivelati: usize) {
+        self.bit_riconciliazione = Some(bit_rivelati);
+    }
+
+    /// Pubblicazione del tag di verifica della chiave (scrittore -> lettore)
+    pub fn pubblica_tag_verifica(&mut self, tag: TagVerifica) {
+        self.tag_verifica = Some(tag);
+    }
+
+    /// Bit della chiave rivelati dal tag di verifica pubblicato (0 se non pubblicato)
+    pub fn bit_tag_verifica(&self) -> usize {
+        self.tag_verifica.as_ref().map_or(0, |t| t.tag.len())
+    }
+
+    /// Esito del confronto dei tag di verifica (lettore -> scrittore)
+    pub fn pubblica_esito_verifica(&mut self, chiave_verificata: bool) {
+        self.chiave_verificata = Some(chiave_verificata);
+    }
+
+    /// Spedizione sequenza rilevazioni: quali slot hanno prodotto un click (lettore -> scrittore)
+    pub fn invia_sequenza_rilevazioni(&mut self, rilevazioni: VettoreBit) {
+        assert_eq!(rilevazioni.len(), self.lunghezza);
+        self.sequenza_rilevazioni = rilevazioni;
+    }
+
+    /// Lettura sequenza rilevazioni (scrittore)
+    pub fn leggi_sequenza_rilevazioni(&self) -> &VettoreBit {
+        &self.sequenza_rilevazioni
     }
 
     /// Fine lettura (lettore -> scrittore)
@@ -55,28 +175,28 @@ impl PublicChannel {
     }
 
     /// Spedizione sequenza ricezione fotoni (lettore -> scrittore)
-    pub fn invia_sequenza_ricezione(&mut self, esito: Vec<bool>) {
-        assert_eq!(esito.len(), lung_msg());
+    pub fn invia_sequenza_ricezione(&mut self, esito: VettoreBit) {
+        assert_eq!(esito.len(), self.lunghezza);
         self.sequenza_ricezione = esito;
         self.sequenza_polarizzazioni_pronta = true;
     }
 
     /// Lettura sequenza ricezione fotoni (scrittore)
-    pub fn leggi_sequenza_ricezione(&mut self) -> Vec<bool> {
+    pub fn leggi_sequenza_ricezione(&mut self) -> &VettoreBit {
         self.sequenza_polarizzazioni_pronta = false;
-        self.sequenza_ricezione.clone()
+        &self.sequenza_ricezione
     }
 
     /// Scrittura test avversario (lettore)
-    pub fn scrivi_test_avversario(&mut self, test: Vec<u8>) {
+    pub fn scrivi_test_avversario(&mut self, test: VettoreBit) {
         self.test_avversario = test;
         self.test_avversario_pronto = true;
     }
 
     /// Lettura test avversario (scrittore)
-    pub fn leggi_test_avversario(&mut self) -> Vec<u8> {
+    pub fn leggi_test_avversario(&mut self) -> &VettoreBit {
         self.test_avversario_pronto = false;
-        self.test_avversario.clone()
+        &self.test_avversario
     }
 
     /// Segnala che la chiave simmetrica è stata definita correttamente (scrittore)
@@ -84,6 +204,16 @@ impl PublicChannel {
         self.chiave_simmetrica_ok = true;
     }
 
+    /// Segnala che le contromisure del rivelatore hanno rilevato un accecamento (lettore)
+    pub fn segnala_allarme_rivelatore(&mut self) {
+        self.allarme_rivelatore = true;
+    }
+
+    /// Segnala che la chiave è stata scartata per la presenza di un avversario (scrittore)
+    pub fn segnala_avversario_rilevato(&mut self) {
+        self.avversario_rilevato = true;
+    }
+
     /// Segnala che il processo è terminato (scrittore)
     pub fn processo_terminato(&mut self) {
         self.processo_terminato = true;
@@ -1,12 +1,15 @@
 use rand::Rng;
 use crate::config::{POL_X, POL_Z};
+use crate::session_rng::generatore;
 
 /// Canale Quantistico
-/// Mantiene una tupla (polarizzazione, valore) e un flag Fotone_IN.
+/// Mantiene una tupla (polarizzazione, valore), il numero di fotoni dell'impulso e un flag Fotone_IN.
 #[derive(Debug, Clone)]
 pub struct QuantumChannel {
     pub canale_quantistico: (char, u8), // (polarizzazione, valore)
+    pub numero_fotoni: u32,             // fotoni nell'impulso corrente (1 con sorgente ideale, 0 = impulso vuoto)
     pub fotone_in: bool,                // true se è presente un fotone nel canale
+    pub luce_accecante: bool,           // true se l'impulso è luce intensa dell'avversario (stato forzato)
 }
 
 impl QuantumChannel {
@@ -14,7 +17,9 @@ impl QuantumChannel {
     pub fn new() -> Self {
         Self {
             canale_quantistico: (' ', 0),
+            numero_fotoni: 0,
             fotone_in: false,
+            luce_accecante: false,
         }
     }
 
@@ -23,8 +28,27 @@ impl QuantumChannel {
         assert!(polarizzazione == POL_Z || polarizzazione == POL_X);
         assert!(valore == 0 || valore == 1);
         self.canale_quantistico = (polarizzazione, valore);
+        self.numero_fotoni = 1;
+        self.luce_accecante = false;
     }
 
+    /// Spedizione di un impulso della sorgente laser (scrittore): tutti i fotoni
+    /// dell'impulso condividono polarizzazione e valore; `numero_fotoni` può essere 0
+    pub fn spedizione_impulso(&mut self, polarizzazione: char, valore: u8, numero_fotoni: u32) {
+        self.spedizione_fotone(polarizzazione, valore);
+        self.numero_fotoni = numero_fotoni;
+    }
+
+    /// Stato forzato dell'avversario: luce intensa che acceca i rivelatori e impulso di innesco
+    /// con polarizzazione e valore scelti, che fa scattare solo il rivelatore corrispondente
+    pub fn spedizione_stato_forzato(&mut self, polarizzazione: char, valore: u8) {
+        self.spedizione_fotone(polarizzazione, valore);
+        self.luce_accecante = true;
+    }
+
+    /// true se l'impulso contiene almeno un fotone rilevabile
+    pub fn fotone_presente(&self) -> bool { self.numero_fotoni > 0 }
+
     /// Lettura del fotone (lettore/avversario)
     /// Se la polarizzazione di misura coincide, restituisce il valore.
     /// Se differisce, randomizza il valore (0/1), lo scrive e lo restituisce.
@@ -36,7 +60,7 @@ impl QuantumChannel {
             val
         } else {
             // Polarizzazione diversa: il valore collassa casualmente e sostituisce il precedente
-            let mut rng = rand::thread_rng();
+            let mut rng = generatore();
             let nuovo = rng.gen_range(0..=1);
             self.canale_quantistico = (pol_tx, nuovo);
             nuovo
@@ -1,67 +1,155 @@
-use crate::config::lung_msg;
+use crate::bit_vector::{SequenzaFotoni, VettoreBit};
+use crate::config::{attiva_chiave_finita, attiva_decoy, attiva_rivelatore, bit_da_base, lung_msg, POL_X, POL_Z};
+use crate::decoy::{stima_parametri, ClasseIntensita, StimaDecoy};
+use crate::detector::Detector;
+use crate::finite_key::{stima_da_config, StimaChiaveFinita};
+use crate::privacy_amplification::comprimi_chiave;
 use crate::public_channel::PublicChannel;
+use crate::test_sampling::{selezione_bit_test, separa_test_e_chiave};
 
 /// Lettore
 /// Legge i fotoni, confronta le polarizzazioni pubblicate e invia esito/chiavi.
 pub struct Reader {
-    pub messaggio_quantistico_ricevuto: Vec<(char, u8)>,
-    pub esito_letture: Vec<bool>,
-    pub chiave_grezza: Vec<u8>,
-    pub chiave_simmetrica: Vec<u8>,
-    pub test_avversario: Vec<u8>,
+    pub messaggio_quantistico_ricevuto: SequenzaFotoni, // (Z, 0) negli slot senza rilevazione
+    pub rilevazioni: VettoreBit,                        // true se lo slot ha prodotto una rilevazione
+    pub esito_letture: VettoreBit,
+    pub chiave_grezza: VettoreBit,
+    pub basi_chiave_grezza: VettoreBit, // base di ogni bit della chiave grezza, Z -> 0, X -> 1 (vuoto in SARG04)
+    pub selezione_test: VettoreBit,     // true per i bit della chiave grezza usati per il test
+    pub chiave_simmetrica: VettoreBit,
+    pub test_avversario: VettoreBit,
+    // Decoy-state: classi annunciate dallo scrittore e bit esca/vuoto da rivelare per la stima
+    pub classi_intensita: Vec<ClasseIntensita>,
+    pub bit_stima_decoy: Vec<u8>,
+    pub stima_decoy: Option<StimaDecoy>,
+    pub rivelatore: Option<Detector>, // None: lettura ideale
+    pub stima_chiave_finita: Option<StimaChiaveFinita>,
+    pub chiave_verificata: bool, // true se il tag calcolato coincide con quello pubblicato dallo scrittore
 }
 
 impl Reader {
     pub fn new() -> Self {
         Self {
-            messaggio_quantistico_ricevuto: Vec::with_capacity(lung_msg()),
-            esito_letture: vec![false; lung_msg()],
-            chiave_grezza: Vec::new(),
-            chiave_simmetrica: Vec::new(),
-            test_avversario: Vec::new(),
+            messaggio_quantistico_ricevuto: SequenzaFotoni::con_capacita(lung_msg()),
+            rilevazioni: VettoreBit::con_capacita(lung_msg()),
+            esito_letture: VettoreBit::zeri(lung_msg()),
+            chiave_grezza: VettoreBit::new(),
+            basi_chiave_grezza: VettoreBit::new(),
+            selezione_test: VettoreBit::new(),
+            chiave_simmetrica: VettoreBit::new(),
+            test_avversario: VettoreBit::new(),
+            classi_intensita: Vec::new(),
+            bit_stima_decoy: Vec::new(),
+            stima_decoy: None,
+            rivelatore: if attiva_rivelatore() { Some(Detector::da_config_con_contromisure()) } else { None },
+            stima_chiave_finita: None,
+            chiave_verificata: false,
         }
     }
 
     // Nota: la funzione `leggi_tutti_i_fotoni` è stata rimossa perché non utilizzata
     // nel flusso principale; la lettura viene gestita esplicitamente in `main.rs`.
 
-    /// Fine lettura sul canale pubblico
+    /// Registra l'esito della lettura di uno slot: `None` se non c'è stata alcuna rilevazione
+    /// (la misura registrata in quello slot non ha significato e vale (Z, 0))
+    pub fn registra_misura(&mut self, misura: Option<(char, u8)>) {
+        self.rilevazioni.push(misura.is_some());
+        self.messaggio_quantistico_ricevuto.push(misura.unwrap_or((POL_Z, 0)));
+    }
+
+    /// Fine lettura sul canale pubblico, con la sequenza degli slot che hanno prodotto una rilevazione
+    /// e l'eventuale allarme delle contromisure all'accecamento del rivelatore
     pub fn segnala_fine_lettura(&self, p: &mut PublicChannel) {
+        if self.rivelatore.as_ref().is_some_and(|r| r.allarme_accecamento()) {
+            traccia!("[Lettore]: Contromisure del rivelatore: rilevato accecamento");
+            p.segnala_allarme_rivelatore();
+        }
+        p.invia_sequenza_rilevazioni(self.rilevazioni.clone());
         p.fine_lettura();
     }
 
-    /// Confronta polarizzazioni pubblicate dallo scrittore e invia esito letture
-    pub fn elabora_e_invia_esito(&mut self, p: &mut PublicChannel, polarizzazioni_scrittore: Vec<char>) {
-        // Itera insieme alle polarizzazioni pubblicate evitando l'uso di `0..LUNG_MSG`.
-        for (i, &pol_pub) in polarizzazioni_scrittore.iter().enumerate() {
-            let (pol_let, _) = self.messaggio_quantistico_ricevuto[i];
-            self.esito_letture[i] = pol_let == pol_pub;
-        }
+    /// Confronta polarizzazioni pubblicate dallo scrittore e invia esito letture.
+    /// L'esito è positivo solo per gli slot rilevati con polarizzazione uguale a quella dello scrittore:
+    /// rilevazioni AND NOT (basi lettore XOR basi pubblicate), calcolato per parole.
+    pub fn elabora_e_invia_esito(&mut self, p: &mut PublicChannel) {
+        let basi_diverse = self.messaggio_quantistico_ricevuto.basi.xor(p.leggi_polarizzazioni());
+        self.esito_letture = self.rilevazioni.and(&basi_diverse.negato());
         p.invia_sequenza_ricezione(self.esito_letture.clone());
-        println!("[Lettore]: Invio esito confronto polarizzazioni completato");
+        traccia!("[Lettore]: Invio esito confronto polarizzazioni completato");
+
+        // Costruisce chiave grezza (valori con polarizzazione allineata).
+        // Con il decoy-state solo gli impulsi segnale vanno in chiave, gli altri servono alla stima.
+        let mut selezionati = self.esito_letture.clone();
+        self.bit_stima_decoy.clear();
+        if !self.classi_intensita.is_empty() {
+            let segnale: VettoreBit = self.classi_intensita.iter().map(|&c| c == ClasseIntensita::Segnale).collect();
+            for i in selezionati.and(&segnale.negato()).uni() {
+                self.bit_stima_decoy.push(self.messaggio_quantistico_ricevuto[i].1);
+            }
+            selezionati = selezionati.and(&segnale);
+        }
+        self.chiave_grezza = self.messaggio_quantistico_ricevuto.valori.seleziona(&selezionati);
+        self.basi_chiave_grezza = self.messaggio_quantistico_ricevuto.basi.seleziona(&selezionati);
 
-        // Costruisce chiave grezza (valori con polarizzazione allineata)
+        self.prepara_test_e_chiave_simmetrica();
+    }
+
+    /// SARG04: confronta il valore misurato con la coppia di stati annunciata dallo scrittore.
+    /// Se il valore misurato è diverso da quello dello stato della coppia nella base di misura,
+    /// quello stato è escluso e il fotone era l'altro stato della coppia (esito conclusivo):
+    /// il bit inferito è la base dell'altro stato. Altrimenti l'esito è inconclusivo e il fotone è scartato.
+    pub fn elabora_e_invia_esito_sarg04(&mut self, p: &mut PublicChannel) {
         self.chiave_grezza.clear();
-        for i in 0..lung_msg() {
+        for (i, &(val_z, val_x)) in p.leggi_coppie_stati().iter().enumerate() {
+            let (pol_let, val_let) = self.messaggio_quantistico_ricevuto[i];
+            if !self.rilevazioni[i] {
+                // Nessuna rilevazione: esito inconclusivo
+                self.esito_letture.imposta(i, false);
+                continue;
+            }
+            let (val_candidato, pol_altra) = if pol_let == POL_Z { (val_z, POL_X) } else { (val_x, POL_Z) };
+            self.esito_letture.imposta(i, val_let != val_candidato);
             if self.esito_letture[i] {
-                let (_, val) = self.messaggio_quantistico_ricevuto[i];
-                self.chiave_grezza.push(val);
+                self.chiave_grezza.push(bit_da_base(pol_altra) == 1);
             }
         }
+        p.invia_sequenza_ricezione(self.esito_letture.clone());
+        traccia!("[Lettore]: Invio esiti conclusivi SARG04 completato");
 
-        // Prepara test avversario (un bit ogni 8, partendo dal primo)
-        self.test_avversario.clear();
-        let mut idx = 0usize;
-        while idx < self.chiave_grezza.len() {
-            self.test_avversario.push(self.chiave_grezza[idx]);
-            idx += 8;
-        }
+        self.prepara_test_e_chiave_simmetrica();
+    }
 
-        // Chiave simmetrica locale (rimuove i bit usati per test)
-        self.chiave_simmetrica.clear();
-        for (i, b) in self.chiave_grezza.iter().enumerate() {
-            if i % 8 != 0 { self.chiave_simmetrica.push(*b); }
-        }
+    /// Accumulo di più round: accoda misure e chiave grezza di un round successivo e ricalcola
+    /// test e chiave simmetrica locale sulla chiave grezza accumulata
+    pub fn accoda_round(&mut self, altro: Reader) {
+        self.messaggio_quantistico_ricevuto.estendi(&altro.messaggio_quantistico_ricevuto);
+        self.rilevazioni.estendi(&altro.rilevazioni);
+        self.esito_letture.estendi(&altro.esito_letture);
+        self.chiave_grezza.estendi(&altro.chiave_grezza);
+        self.basi_chiave_grezza.estendi(&altro.basi_chiave_grezza);
+        self.classi_intensita.extend(altro.classi_intensita);
+        self.bit_stima_decoy.extend(altro.bit_stima_decoy);
+        self.prepara_test_e_chiave_simmetrica();
+    }
+
+    /// Prepara test avversario (stessa selezione dello scrittore, calcolata sulle basi pubbliche)
+    /// e la chiave simmetrica locale (chiave grezza senza i bit usati per il test)
+    fn prepara_test_e_chiave_simmetrica(&mut self) {
+        self.selezione_test = selezione_bit_test(&self.basi_chiave_grezza, self.chiave_grezza.len());
+        let (test, chiave) = separa_test_e_chiave(&self.chiave_grezza, &self.selezione_test);
+        self.test_avversario = test;
+        self.chiave_simmetrica = chiave;
+    }
+
+    /// Decoy-state: legge le classi di intensità annunciate dallo scrittore
+    pub fn leggi_classi_intensita(&mut self, p: &PublicChannel) {
+        self.classi_intensita = p.leggi_classi_intensita().to_vec();
+    }
+
+    /// Decoy-state: rivela i bit esca/vuoto selezionati (le rilevazioni per classe sono già
+    /// pubbliche tramite la sequenza delle rilevazioni)
+    pub fn invia_dati_stima_decoy(&self, p: &mut PublicChannel) {
+        p.invia_dati_stima_decoy(self.bit_stima_decoy.clone());
     }
 
     /// Scrive sul canale pubblico il test avversario
@@ -69,12 +157,41 @@ impl Reader {
         p.scrivi_test_avversario(self.test_avversario.clone());
     }
 
-    /// Attende processo terminato e stampa conferma finale
+    /// Calcola il tag della propria chiave con il seme pubblicato, lo confronta e pubblica l'esito
+    pub fn verifica_chiave(&mut self, p: &mut PublicChannel) {
+        self.chiave_verificata = p.tag_verifica.as_ref().is_some_and(|t| t.verifica(&self.chiave_simmetrica));
+        if self.chiave_verificata {
+            traccia!("[Lettore]: Tag di verifica coincidente");
+        } else {
+            traccia!("[Lettore]: Tag di verifica diverso");
+        }
+        p.pubblica_esito_verifica(self.chiave_verificata);
+    }
+
+    /// Attende processo terminato e conferma la chiave solo se la verifica dei tag è stata superata
     pub fn conferma_finale(&mut self, p: &PublicChannel) {
-        if p.chiave_simmetrica_ok {
-            println!("[Lettore]: Confermo definizione chiave simmetrica {:?}", self.chiave_simmetrica);
+        let chiave_valida = p.chiave_simmetrica_ok && self.chiave_verificata;
+        if chiave_valida && attiva_decoy() {
+            // Stessa stima dello scrittore sulle statistiche pubbliche e stessa compressione
+            let stima = stima_parametri(&p.statistiche_decoy, self.chiave_simmetrica.len());
+            let lunghezza = stima.lunghezza_chiave.saturating_sub(p.bit_tag_verifica());
+            self.chiave_simmetrica = comprimi_chiave(&self.chiave_simmetrica, lunghezza, &p.seme_amplificazione);
+            self.stima_decoy = Some(stima);
+        }
+        if chiave_valida && attiva_chiave_finita() {
+            // Errori di test e bit rivelati in riconciliazione sono pubblici: stessa analisi e stessa compressione dello scrittore
+            let stima = stima_da_config(self.chiave_simmetrica.len(), self.test_avversario.len(), p.errori_test, p.bit_riconciliazione);
+            self.chiave_simmetrica = comprimi_chiave(&self.chiave_simmetrica, stima.lunghezza_chiave, &p.seme_amplificazione);
+            self.stima_chiave_finita = Some(stima);
+        }
+        if chiave_valida {
+            traccia!("[Lettore]: Confermo definizione chiave simmetrica {:?}", self.chiave_simmetrica);
         } else {
-            println!("[Lettore]: Confermo presenza avversari, chiave simmetrica cancellata");
+            if p.avversario_rilevato {
+                traccia!("[Lettore]: Confermo presenza avversari, chiave simmetrica cancellata");
+            } else {
+                traccia!("[Lettore]: Chiave scartata senza rilevare avversari, chiave simmetrica cancellata");
+            }
             // Cancella il valore della chiave simmetrica
             self.chiave_simmetrica.clear();
         }
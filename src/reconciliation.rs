@@ -0,0 +1,346 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use crate::bit_vector::VettoreBit;
+use crate::config::{riconciliazione, TipoRiconciliazione};
+use crate::information::entropia_binaria;
+use crate::session_rng::generatore;
+
+// Riconciliazione (correzione degli errori) delle chiavi di scrittore e lettore.
+// Lo scrittore pubblica informazione sulla propria chiave (parità, sindromi) e il lettore corregge la sua.
+// Ogni bit pubblicato è informazione concessa all'avversario (leak) e va tolto con l'amplificazione della privacy.
+
+/// Risultato della riconciliazione di una coppia di chiavi
+#[derive(Debug, Clone)]
+pub struct EsitoRiconciliazione {
+    pub metodo: &'static str,
+    pub bit_chiave: usize,
+    pub errori_iniziali: usize, // bit diversi prima della riconciliazione
+    pub bit_rivelati: usize,    // leak: bit pubblicati sul canale pubblico
+    pub round: usize,           // scambi di messaggi sul canale pubblico
+    pub errori_residui: usize,  // bit ancora diversi dopo la riconciliazione
+    pub interrotta: bool,       // perdita oltre il limite ammesso: il blocco va scartato
+    pub chiave_corretta: VettoreBit, // chiave del lettore dopo la correzione
+}
+
+/// Esito pubblico del test: errori osservati sui bit di test confrontati
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CampioneTest {
+    pub errori: usize,
+    pub bit: usize,
+}
+
+impl CampioneTest {
+    /// QBER osservato sul campione
+    pub fn qber(&self) -> f64 {
+        if self.bit == 0 { 0.0 } else { self.errori as f64 / self.bit as f64 }
+    }
+
+    /// Limite superiore del QBER a `z` deviazioni standard (intervallo di Wilson): resta positivo anche
+    /// senza errori osservati e vale 1/2 senza campione
+    pub fn qber_massimo(&self, z: f64) -> f64 {
+        if self.bit == 0 {
+            return 0.5;
+        }
+        let (k, q, z2) = (self.bit as f64, self.qber(), z * z);
+        let centro = q + z2 / (2.0 * k);
+        let ampiezza = z * (q * (1.0 - q) / k + z2 / (4.0 * k * k)).sqrt();
+        ((centro + ampiezza) / (1.0 + z2 / k)).min(0.5)
+    }
+}
+
+impl EsitoRiconciliazione {
+    /// Tasso d'errore effettivo tra le chiavi prima della riconciliazione
+    pub fn qber(&self) -> f64 {
+        if self.bit_chiave == 0 { 0.0 } else { self.errori_iniziali as f64 / self.bit_chiave as f64 }
+    }
+
+    /// Efficienza f = leak / (n · h(QBER)); 1 è il limite di Shannon. `None` senza errori iniziali.
+    pub fn efficienza(&self) -> Option<f64> {
+        let minimo = self.bit_chiave as f64 * entropia_binaria(self.qber());
+        if minimo > 0.0 { Some(self.bit_rivelati as f64 / minimo) } else { None }
+    }
+}
+
+/// Schema di riconciliazione: corregge la chiave del lettore verso quella dello scrittore
+/// usando il campione di test per stimare il QBER
+pub trait Reconciler {
+    fn nome(&self) -> &'static str;
+    fn riconcilia(&self, chiave_scrittore: &VettoreBit, chiave_lettore: &VettoreBit, test: CampioneTest) -> EsitoRiconciliazione;
+}
+
+/// Riconciliatore configurato (variabile `RICONCILIAZIONE`); `None` se la riconciliazione è disattivata
+pub fn riconciliatore_da_config() -> Option<Box<dyn Reconciler>> {
+    match riconciliazione() {
+        TipoRiconciliazione::Nessuna => None,
+        TipoRiconciliazione::Winnow => Some(Box::new(Winnow::default())),
+        TipoRiconciliazione::Ldpc => Some(Box::new(Ldpc::default())),
+    }
+}
+
+/// Winnow: a ogni passata la chiave (permutata con una permutazione pubblica) è divisa in blocchi di 2^m bit.
+/// Si scambiano le parità dei blocchi; per i blocchi discordi lo scrittore pubblica la sindrome di Hamming
+/// (m bit, XOR degli indici dei bit a 1) e il lettore inverte il bit indicato dalla differenza delle sindromi.
+/// I blocchi crescono quando meno di un blocco su 8 è discorde; ci si ferma dopo due passate senza blocchi discordi.
+/// Confrontare le parità equivale a calcolare la parità dei blocchi della differenza (XOR) delle chiavi permutate,
+/// fatto per parole da 64 bit.
+pub struct Winnow {
+    pub passate_max: usize,
+}
+
+impl Default for Winnow {
+    fn default() -> Self {
+        Self { passate_max: 32 }
+    }
+}
+
+impl Reconciler for Winnow {
+    fn nome(&self) -> &'static str {
+        "Winnow"
+    }
+
+    fn riconcilia(&self, chiave_scrittore: &VettoreBit, chiave_lettore: &VettoreBit, test: CampioneTest) -> EsitoRiconciliazione {
+        let n = chiave_scrittore.len();
+        let qber_stimato = test.qber();
+        let mut corretta = chiave_lettore.clone();
+        let mut bit_rivelati = 0;
+        let mut round = 0;
+        // Blocco iniziale con circa mezzo errore atteso
+        let mut m = if qber_stimato > 0.0 { ((0.5 / qber_stimato).log2().round() as u32).clamp(2, 10) } else { 10 };
+        let mut passate_pulite = 0;
+        let mut indici: Vec<usize> = (0..n).collect();
+        let mut rng = generatore();
+
+        for passata in 0..self.passate_max {
+            if n == 0 {
+                break;
+            }
+            if passata > 0 {
+                indici.shuffle(&mut rng);
+            }
+            let blocco = 1usize << m;
+            let permutata = |k: &VettoreBit| -> VettoreBit { indici.iter().map(|&i| k[i]).collect() };
+            let differenza = permutata(chiave_scrittore).xor(&permutata(&corretta));
+
+            // Round 1: parità dei blocchi
+            round += 1;
+            let blocchi = n.div_ceil(blocco);
+            bit_rivelati += blocchi;
+            let discordi: Vec<usize> =
+                (0..blocchi).filter(|&b| differenza.parita_intervallo(b * blocco, (b + 1) * blocco) == 1).collect();
+
+            // Pochi blocchi discordi: gli errori residui sono rari, blocchi più grandi alla passata successiva
+            if discordi.len() * 8 < blocchi {
+                m = (m + 1).min(16);
+            }
+            if discordi.is_empty() {
+                passate_pulite += 1;
+                if passate_pulite >= 2 {
+                    break;
+                }
+            } else {
+                passate_pulite = 0;
+                // Round 2: sindromi di Hamming dei blocchi discordi
+                round += 1;
+                for b in discordi {
+                    let inizio = b * blocco;
+                    let indici_blocco = &indici[inizio..(inizio + blocco).min(n)];
+                    bit_rivelati += m as usize;
+                    // Differenza delle sindromi: XOR delle posizioni in cui le chiavi differiscono
+                    let posizione = (0..indici_blocco.len()).filter(|&pos| differenza[inizio + pos]).fold(0usize, |acc, pos| acc ^ pos);
+                    if let Some(&i) = indici_blocco.get(posizione) {
+                        corretta.inverti(i);
+                    }
+                }
+            }
+        }
+
+        EsitoRiconciliazione {
+            metodo: self.nome(),
+            bit_chiave: n,
+            errori_iniziali: chiave_scrittore.differenze(chiave_lettore),
+            bit_rivelati,
+            round,
+            errori_residui: chiave_scrittore.differenze(&corretta),
+            interrotta: false,
+            chiave_corretta: corretta,
+        }
+    }
+}
+
+/// LDPC con decodifica della sindrome tramite belief propagation (somma-prodotto in forma LLR).
+/// Lo scrittore pubblica la sindrome H·x di una matrice sparsa casuale (pubblica) con peso di colonna 3 e
+/// f_iniziale · n · h(Q) righe; il lettore cerca l'errore e con H·e = H·x ⊕ H·y. Q è il limite superiore del QBER
+/// a `z_iniziale` deviazioni standard dal campione di test, così un campione senza errori non azzera la stima.
+/// Se la decodifica non converge lo scrittore rivela altre righe sparse (tasso adattivo); oltre f_max · n · h(Q_max),
+/// con Q_max a `z_massimo` deviazioni standard, il blocco è interrotto invece di rivelare l'intera chiave
+/// (f_max = `efficienza_massima` copre l'inefficienza della decodifica su blocchi corti).
+pub struct Ldpc {
+    pub efficienza_iniziale: f64,
+    pub incremento: f64, // righe aggiunte a ogni tentativo, in frazione di n · h(Q)
+    pub iterazioni_max: usize,
+    pub z_iniziale: f64,
+    pub z_massimo: f64,
+    pub efficienza_massima: f64,
+}
+
+impl Default for Ldpc {
+    fn default() -> Self {
+        Self { efficienza_iniziale: 1.1, incremento: 0.1, iterazioni_max: 60, z_iniziale: 1.0, z_massimo: 3.0, efficienza_massima: 1.5 }
+    }
+}
+
+impl Ldpc {
+    /// Righe sparse: ciascuna delle `colonne` partecipa a `min(3, righe)` righe distinte scelte a caso
+    fn matrice_sparsa(colonne: impl Iterator<Item = usize>, righe: usize) -> Vec<Vec<usize>> {
+        let mut rng = generatore();
+        let mut h = vec![Vec::new(); righe];
+        let indici_righe: Vec<usize> = (0..righe).collect();
+        for colonna in colonne {
+            for &riga in indici_righe.choose_multiple(&mut rng, 3.min(righe)) {
+                h[riga].push(colonna);
+            }
+        }
+        for riga in &mut h {
+            riga.sort_unstable();
+        }
+        h
+    }
+
+    /// Righe aggiuntive (tasso adattivo) costruite come la matrice iniziale: colonne scelte a caso con peso 3
+    /// nelle righe nuove, in numero tale che le righe abbiano il peso `peso_riga` di quelle iniziali
+    fn righe_aggiuntive(n: usize, righe: usize, peso_riga: usize) -> Vec<Vec<usize>> {
+        let colonne: Vec<usize> = (0..n).collect();
+        let scelte = (righe * peso_riga).div_ceil(3).clamp(1, n);
+        let scelte = colonne.choose_multiple(&mut generatore(), scelte).copied().collect::<Vec<_>>();
+        Self::matrice_sparsa(scelte.into_iter(), righe)
+    }
+
+    fn sindrome(h: &[Vec<usize>], chiave: &VettoreBit) -> VettoreBit {
+        h.iter().map(|riga| riga.iter().fold(false, |acc, &j| acc ^ chiave[j])).collect()
+    }
+
+    /// Belief propagation (flooding): ritorna l'errore stimato se soddisfa la sindrome entro `iterazioni_max`
+    fn decodifica(&self, h: &[Vec<usize>], sindrome: &VettoreBit, n: usize, llr: f64) -> Option<VettoreBit> {
+        let mut stima = VettoreBit::zeri(n);
+        if Self::sindrome(h, &stima) == *sindrome {
+            return Some(stima);
+        }
+        let mut messaggi: Vec<Vec<f64>> = h.iter().map(|riga| vec![0.0; riga.len()]).collect();
+        let mut totale = vec![llr; n];
+        const LIMITE: f64 = 1.0 - 1e-12;
+
+        for _ in 0..self.iterazioni_max {
+            let mut nuovo_totale = vec![llr; n];
+            for (i, riga) in h.iter().enumerate() {
+                // tanh dei messaggi variabile -> controllo, esclusa la riga stessa
+                let t: Vec<f64> = riga.iter().zip(messaggi[i].iter()).map(|(&j, &r)| ((totale[j] - r) / 2.0).tanh()).collect();
+                // prodotti prefisso/suffisso per escludere ogni variabile in O(grado)
+                let mut suffisso = vec![1.0; t.len() + 1];
+                for k in (0..t.len()).rev() {
+                    suffisso[k] = suffisso[k + 1] * t[k];
+                }
+                let segno = if sindrome[i] { -1.0 } else { 1.0 };
+                let mut prefisso = 1.0;
+                for (k, &j) in riga.iter().enumerate() {
+                    let prodotto = (prefisso * suffisso[k + 1]).clamp(-LIMITE, LIMITE);
+                    messaggi[i][k] = segno * 2.0 * prodotto.atanh();
+                    nuovo_totale[j] += messaggi[i][k];
+                    prefisso *= t[k];
+                }
+            }
+            totale = nuovo_totale;
+            stima = totale.iter().map(|&l| l < 0.0).collect();
+            if Self::sindrome(h, &stima) == *sindrome {
+                return Some(stima);
+            }
+        }
+        None
+    }
+}
+
+impl Reconciler for Ldpc {
+    fn nome(&self) -> &'static str {
+        "LDPC"
+    }
+
+    fn riconcilia(&self, chiave_scrittore: &VettoreBit, chiave_lettore: &VettoreBit, test: CampioneTest) -> EsitoRiconciliazione {
+        let n = chiave_scrittore.len();
+        let q = test.qber_massimo(self.z_iniziale).clamp(1e-6, 0.45);
+        let llr = ((1.0 - q) / q).ln();
+        let limite = ((self.efficienza_massima * n as f64 * entropia_binaria(test.qber_massimo(self.z_massimo))).floor() as usize).min(n);
+        let righe_iniziali = ((self.efficienza_iniziale * n as f64 * entropia_binaria(q)).ceil() as usize).clamp(8.min(n), limite.max(8.min(n)));
+        let passo = ((self.incremento * n as f64 * entropia_binaria(q)).ceil() as usize).max(8);
+        let peso_riga = if righe_iniziali == 0 { 0 } else { (3 * n).div_ceil(righe_iniziali).max(3) };
+
+        let mut h = Self::matrice_sparsa(0..n, righe_iniziali);
+        let mut round = 0;
+        let mut corretta = chiave_lettore.clone();
+        let mut interrotta = false;
+        loop {
+            // Lo scrittore pubblica la sindrome (le righe nuove); il lettore tenta la decodifica
+            round += 1;
+            let sindrome = Self::sindrome(&h, chiave_scrittore).xor(&Self::sindrome(&h, chiave_lettore));
+            if let Some(errore) = self.decodifica(&h, &sindrome, n, llr) {
+                corretta = corretta.xor(&errore);
+                break;
+            }
+            if h.len() >= limite {
+                interrotta = true;
+                break;
+            }
+            let aggiunte = passo.min(limite - h.len());
+            h.extend(Self::righe_aggiuntive(n, aggiunte, peso_riga));
+        }
+
+        EsitoRiconciliazione {
+            metodo: self.nome(),
+            bit_chiave: n,
+            errori_iniziali: chiave_scrittore.differenze(chiave_lettore),
+            bit_rivelati: h.len(),
+            round,
+            errori_residui: chiave_scrittore.differenze(&corretta),
+            interrotta,
+            chiave_corretta: corretta,
+        }
+    }
+}
+
+/// Confronto degli schemi su chiavi sintetiche di `bit` bit con errori indipendenti di probabilità `qber`
+/// (il QBER è stimato da un campione di test sintetico)
+pub fn confronta_riconciliatori(bit: usize, qber: f64, prove: usize) {
+    let schemi: Vec<Box<dyn Reconciler>> = vec![Box::new(Winnow::default()), Box::new(Ldpc::default())];
+    let mut rng = generatore();
+
+    println!("=== Confronto riconciliazione ({} bit, QBER {:.2}%, {} prove) ===", bit, qber * 100.0, prove);
+    println!("{:<8} | {:>12} | {:>8} | {:>7} | {:>15} | {:>8}", "Schema", "Bit rivelati", "f", "Round", "Errori residui", "Fallite");
+    println!("{}", "-".repeat(72));
+    for schema in &schemi {
+        let (mut rivelati, mut efficienza, mut con_efficienza, mut round, mut residui, mut fallite) = (0, 0.0, 0, 0, 0, 0);
+        for _ in 0..prove {
+            let a: VettoreBit = (0..bit).map(|_| rng.gen_bool(0.5)).collect();
+            let b: VettoreBit = a.iter().map(|x| x ^ rng.gen_bool(qber)).collect();
+            // Campione di test come nelle sessioni: un bit selezionato su 8, cioè un settimo della chiave
+            let bit_test = bit / 7;
+            let test = CampioneTest { errori: (0..bit_test).filter(|_| rng.gen_bool(qber)).count(), bit: bit_test };
+            let esito = schema.riconcilia(&a, &b, test);
+            rivelati += esito.bit_rivelati;
+            if let Some(f) = esito.efficienza() {
+                efficienza += f;
+                con_efficienza += 1;
+            }
+            round += esito.round;
+            residui += esito.errori_residui;
+            if esito.errori_residui > 0 { fallite += 1; }
+        }
+        let media = |x: usize| x as f64 / prove.max(1) as f64;
+        println!(
+            "{:<8} | {:>12.1} | {:>8.3} | {:>7.1} | {:>15.2} | {:>8}",
+            schema.nome(),
+            media(rivelati),
+            if con_efficienza == 0 { 0.0 } else { efficienza / con_efficienza as f64 },
+            media(round),
+            media(residui),
+            fallite
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::adversary::Adversary;
+use crate::config::{
+    attiva_avversario, attiva_decoy, base_casuale, lung_msg, protocollo, strategia_avversario, Protocollo, StrategiaAvversario,
+};
+use crate::entangled_source::EntangledSource;
+use crate::link_model::LinkModel;
+use crate::public_channel::PublicChannel;
+use crate::quantum_channel::QuantumChannel;
+use crate::reader::Reader;
+use crate::reconciliation::{riconciliatore_da_config, CampioneTest, EsitoRiconciliazione};
+use crate::writer::Writer;
+
+/// Sessione del protocollo
+/// Stato finale di attori e canale pubblico al termine di un'esecuzione, usato per tabelle e statistiche.
+pub struct Sessione {
+    pub scrittore: Writer,
+    pub lettore: Reader,
+    pub avversario: Adversary,
+    pub canale_pubblico: PublicChannel,
+    pub collegamento: LinkModel,
+    pub riconciliazione: Option<EsitoRiconciliazione>, // None se disattivata o se il test non è superato
+}
+
+/// Esegue una sessione completa del protocollo configurato sul collegamento indicato
+/// (passi 1-9 della specifica) e restituisce lo stato finale di attori e canale pubblico.
+pub fn esegui_sessione(collegamento: &LinkModel) -> Sessione {
+    let mut sessione = esegui_trasmissione(collegamento);
+    sessione.riconciliazione = post_elaborazione(&mut sessione.scrittore, &mut sessione.lettore, &mut sessione.canale_pubblico);
+    sessione
+}
+
+/// Trasmissione dei fotoni e setaccio (passi 1-6): ritorna attori e canale pubblico con le chiavi grezze
+/// selezionate, pronti per la post-elaborazione (anche dopo l'accumulo di più round)
+pub fn esegui_trasmissione(collegamento: &LinkModel) -> Sessione {
+    // Inizializza canali e attori
+    let mut canale_pubblico = PublicChannel::new();
+    let mut canale_quantistico = QuantumChannel::new();
+    let mut scrittore = Writer::new();
+    let mut lettore = Reader::new();
+    let mut avversario = Adversary::new();
+
+    match protocollo() {
+        Protocollo::BB84 | Protocollo::SARG04 => {
+            // 1) Scrittore inizializza il messaggio (SARG04 usa la stessa preparazione dei fotoni)
+            scrittore.inizializzazione();
+
+            // 2) Trasmissione sequenziale di LUNG_MSG fotoni sul canale quantistico
+            for i in 0..lung_msg() {
+                // Scrittore spedisce il fotone i e attiva Fotone_IN
+                scrittore.scrivi_su_canale_quantistico(&mut canale_quantistico, i);
+
+                // Avversario (se attivo) legge per primo; il cavallo di Troia sonda l'apparato dello scrittore
+                if attiva_avversario() && strategia_avversario() == StrategiaAvversario::CavalloDiTroia {
+                    avversario.sonda_e_leggi(&mut scrittore, i, &mut canale_quantistico);
+                } else {
+                    avversario.leggi_fotone_se_attivo(&mut canale_quantistico);
+                }
+
+                // Perdite del collegamento prima del rivelatore del lettore
+                collegamento.applica_perdite(&mut canale_quantistico);
+
+                // Lettore legge e resetta Fotone_IN
+                leggi_fotone_lettore(&mut lettore, &mut canale_quantistico);
+            }
+        }
+        Protocollo::BBM92 => {
+            // 1) La sorgente (onesta o controllata dall'avversario) distribuisce LUNG_MSG coppie
+            let mut sorgente = EntangledSource::new();
+            let mut canale_quantistico_scrittore = QuantumChannel::new();
+            if attiva_avversario() {
+                traccia!("[Sorgente]: Sorgente compromessa, coppie preparate dall'avversario");
+            } else {
+                traccia!("[Sorgente]: Distribuzione coppie entangled");
+            }
+
+            // 2) Per ogni coppia misurano prima lo scrittore e poi il lettore
+            for _ in 0..lung_msg() {
+                if attiva_avversario() {
+                    avversario.prepara_coppia_compromessa(&mut sorgente, &mut canale_quantistico_scrittore, &mut canale_quantistico);
+                } else {
+                    sorgente.emetti_coppia(&mut canale_quantistico_scrittore, &mut canale_quantistico);
+                }
+                scrittore.misura_fotone_sorgente(&mut sorgente, &mut canale_quantistico_scrittore, &mut canale_quantistico);
+                collegamento.applica_perdite(&mut canale_quantistico);
+                leggi_fotone_lettore(&mut lettore, &mut canale_quantistico);
+            }
+            traccia!("[Scrittore]: Misura fotoni della sorgente completata");
+        }
+    }
+
+    // 3) Segnalazioni post-lettura (in BBM92 l'avversario agisce sulla sorgente e non legge i fotoni)
+    if attiva_avversario() && protocollo() != Protocollo::BBM92 {
+        traccia!("[Avversario]: Lettura completata");
+    }
+
+    // Il lettore segnala fine lettura
+    lettore.segnala_fine_lettura(&mut canale_pubblico);
+    traccia!("[Lettore]: Lettura completata");
+
+    if protocollo() == Protocollo::SARG04 {
+        // 4) Lo scrittore pubblica, per ogni fotone, una coppia di stati non ortogonali
+        scrittore.pubblicazione_coppie_stati(&mut canale_pubblico);
+
+        // L'avversario (se ha fotoni in memoria) li misura dopo l'annuncio e ne deduce il bit dalla coppia
+        if attiva_avversario() {
+            avversario.misura_memoria_dopo_coppie();
+        }
+
+        // 5) Il lettore legge le coppie, individua gli esiti conclusivi e li invia
+        lettore.elabora_e_invia_esito_sarg04(&mut canale_pubblico);
+
+        // 6) Lo scrittore seleziona la chiave grezza sugli esiti conclusivi
+        if canale_pubblico.sequenza_polarizzazioni_pronta {
+            scrittore.selezione_chiave_grezza_sarg04(&mut canale_pubblico);
+        }
+    } else {
+        // 4) Lo scrittore pubblica le polarizzazioni utilizzate
+        //    (e con il decoy-state le classi di intensità, lette dal lettore)
+        scrittore.pubblicazione_polarizzazione(&mut canale_pubblico);
+        if attiva_decoy() {
+            scrittore.pubblicazione_classi_intensita(&mut canale_pubblico);
+            lettore.leggi_classi_intensita(&canale_pubblico);
+        }
+
+        // L'avversario (se ha fotoni in memoria) ascolta l'annuncio e li misura nella base corretta
+        if attiva_avversario() {
+            avversario.misura_memoria_dopo_annuncio(&canale_pubblico);
+        }
+
+        // 5) Il lettore attende pubblicazione e poi legge polarizzazioni,
+        //    calcola esito letture e lo invia
+        lettore.elabora_e_invia_esito(&mut canale_pubblico);
+
+        // 6) Lo scrittore attende che la sequenza sia pronta, seleziona chiave grezza
+        if canale_pubblico.sequenza_polarizzazioni_pronta {
+            scrittore.selezione_chiave_grezza(&mut canale_pubblico);
+        }
+    }
+
+    Sessione { scrittore, lettore, avversario, canale_pubblico, collegamento: collegamento.clone(), riconciliazione: None }
+}
+
+/// Post-elaborazione delle chiavi grezze (passi 7-9): test, riconciliazione, verifica della chiave,
+/// amplificazione della privacy e conferma finale. Ritorna l'esito della riconciliazione, se eseguita.
+pub fn post_elaborazione(
+    scrittore: &mut Writer,
+    lettore: &mut Reader,
+    canale_pubblico: &mut PublicChannel,
+) -> Option<EsitoRiconciliazione> {
+    // 7) Il lettore invia i bit di test avversario sul canale pubblico
+    //    (e con il decoy-state i dati per la stima dei parametri)
+    lettore.invia_test_avversario(canale_pubblico);
+    if attiva_decoy() {
+        lettore.invia_dati_stima_decoy(canale_pubblico);
+    }
+
+    // 8) Lo scrittore verifica il test e, se superato, riconcilia le chiavi (se configurato),
+    //    verifica con il lettore il tag della chiave, definisce la chiave finale e termina il processo
+    let mut riconciliazione: Option<EsitoRiconciliazione> = None;
+    if scrittore.selezione_test_e_verifica(canale_pubblico) {
+        if let Some(riconciliatore) = riconciliatore_da_config() {
+            // Il lettore corregge la sua chiave con parità e sindromi pubblicate dallo scrittore
+            let test = CampioneTest { errori: canale_pubblico.errori_test, bit: scrittore.test_avversario.len() };
+            let esito = riconciliatore.riconcilia(&scrittore.chiave_simmetrica, &lettore.chiave_simmetrica, test);
+            traccia!(
+                "[Lettore]: Riconciliazione {}: {} bit rivelati in {} round, {} errori residui",
+                esito.metodo, esito.bit_rivelati, esito.round, esito.errori_residui
+            );
+            lettore.chiave_simmetrica = esito.chiave_corretta.clone();
+            canale_pubblico.pubblica_riconciliazione(esito.bit_rivelati);
+            riconciliazione = Some(esito);
+        }
+        if riconciliazione.as_ref().is_some_and(|esito| esito.interrotta) {
+            // Perdita oltre il limite: il blocco è scartato senza verifica
+            scrittore.scarta_chiave_non_riconciliata(canale_pubblico);
+        } else {
+            // Verifica della chiave con tag di hash universale prima dell'amplificazione della privacy
+            scrittore.pubblicazione_tag_verifica(canale_pubblico);
+            lettore.verifica_chiave(canale_pubblico);
+            scrittore.definizione_chiave_finale(canale_pubblico);
+        }
+    }
+
+    // 9) Il lettore attende il termine del processo e conferma l'esito
+    lettore.conferma_finale(canale_pubblico);
+
+    riconciliazione
+}
+
+/// Lettura di un fotone da parte del lettore con polarizzazione casuale (secondo `PROB_BASE_Z`) e reset di Fotone_IN.
+/// Per rispettare la specifica "un fotone alla volta" la lettura avviene esplicitamente qui
+/// e non in un ciclo interno al `Reader`.
+/// Un impulso vuoto non produce alcuna rilevazione, registrata esplicitamente dal lettore.
+/// Con il modello di rivelatore il risultato dipende da efficienza, conteggi di buio, tempo morto e afterpulse.
+fn leggi_fotone_lettore(lettore: &mut Reader, canale_quantistico: &mut QuantumChannel) {
+    let pol_let = base_casuale();
+    let misura = if let Some(rivelatore) = &mut lettore.rivelatore {
+        rivelatore.misura(canale_quantistico, pol_let).map(|val| (pol_let, val))
+    } else if canale_quantistico.fotone_presente() {
+        Some((pol_let, canale_quantistico.lettura_fotone(pol_let)))
+    } else {
+        None
+    };
+    lettore.registra_misura(misura);
+    canale_quantistico.set_fotone_out();
+}
@@ -0,0 +1,55 @@
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::cell::RefCell;
+use crate::config::seme_principale;
+
+// Generatore casuale delle simulazioni: uno per thread, riseminato all'inizio di ogni sessione di batch
+// con un seme derivato dal seme principale e dall'indice della sessione. Ogni sessione dipende quindi solo
+// dal proprio indice e le statistiche aggregate non cambiano con il numero di thread.
+// Gli identificativi dell'archivio delle chiavi restano estratti da `thread_rng` (mai ripetuti).
+
+thread_local! {
+    static GENERATORE: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Accesso al generatore del thread corrente (come `rand::thread_rng`)
+#[derive(Debug, Clone, Copy)]
+pub struct GeneratoreSessione;
+
+/// Generatore casuale del thread corrente
+pub fn generatore() -> GeneratoreSessione {
+    GeneratoreSessione
+}
+
+impl RngCore for GeneratoreSessione {
+    fn next_u32(&mut self) -> u32 {
+        GENERATORE.with(|g| g.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        GENERATORE.with(|g| g.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        GENERATORE.with(|g| g.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        GENERATORE.with(|g| g.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+/// Risemina il generatore del thread corrente per la sessione di indice dato
+pub fn imposta_seme_sessione(indice: u64) {
+    let seme = seme_sessione(seme_principale(), indice);
+    GENERATORE.with(|g| *g.borrow_mut() = StdRng::seed_from_u64(seme));
+}
+
+/// Seme della sessione: SplitMix64 della combinazione di seme principale e indice,
+/// così sessioni con indici vicini hanno semi non correlati
+fn seme_sessione(principale: u64, indice: u64) -> u64 {
+    let mut z = principale.wrapping_add(indice.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,140 @@
+use crate::bit_vector::VettoreBit;
+use crate::config::imposta_verboso;
+use crate::link_model::LinkModel;
+use crate::public_channel::PublicChannel;
+use crate::reader::Reader;
+use crate::session::{esegui_trasmissione, post_elaborazione};
+use crate::writer::Writer;
+
+// Modalità a lunghezza di chiave obiettivo: si eseguono round di trasmissione da LUNG_MSG fotoni
+// accumulando i bit setacciati; quando la chiave grezza accumulata basta (secondo la resa stimata)
+// per i bit mancanti, il blocco accumulato è post-elaborato una sola volta (test, riconciliazione,
+// verifica, amplificazione) e la sua chiave finale è accodata. Il processo si interrompe solo se
+// l'avversario è rilevato o se si raggiunge il numero massimo di round.
+
+/// Frazione della chiave grezza che diventa chiave finale senza amplificazione (1 bit su 8 va al test)
+const RESA_INIZIALE: f64 = 7.0 / 8.0;
+
+/// Risultato della generazione di una chiave di lunghezza obiettivo
+#[derive(Debug, Clone, Default)]
+pub struct RisultatoChiaveObiettivo {
+    pub bit_obiettivo: usize,
+    pub chiave_scrittore: VettoreBit,
+    pub chiave_lettore: VettoreBit,
+    pub round: usize,          // round di trasmissione eseguiti
+    pub fotoni: usize,         // impulsi spediti in tutti i round
+    pub bit_grezzi: usize,     // bit setacciati accumulati
+    pub blocchi: usize,        // post-elaborazioni eseguite
+    pub bit_rivelati: usize,   // bit pubblicati dalla riconciliazione in tutti i blocchi
+    pub bit_test: usize,       // bit di test confrontati in tutti i blocchi
+    pub errori_test: usize,
+    pub avversario_rilevato: bool,
+}
+
+impl RisultatoChiaveObiettivo {
+    /// true se la chiave ha raggiunto la lunghezza richiesta
+    pub fn completata(&self) -> bool {
+        !self.avversario_rilevato && self.chiave_scrittore.len() >= self.bit_obiettivo
+    }
+
+    /// QBER stimato sui bit di test di tutti i blocchi
+    pub fn qber(&self) -> f64 {
+        if self.bit_test == 0 { 0.0 } else { self.errori_test as f64 / self.bit_test as f64 }
+    }
+
+    /// Bit di chiave finale per impulso spedito
+    pub fn tasso_chiave(&self) -> f64 {
+        if self.fotoni == 0 { 0.0 } else { self.chiave_scrittore.len() as f64 / self.fotoni as f64 }
+    }
+
+    /// Frazione dei bit setacciati che è diventata chiave finale
+    pub fn resa_setaccio(&self) -> f64 {
+        if self.bit_grezzi == 0 { 0.0 } else { self.chiave_scrittore.len() as f64 / self.bit_grezzi as f64 }
+    }
+
+    pub fn stampa(&self) {
+        println!("
+=== Chiave obiettivo ({} bit) ===", self.bit_obiettivo);
+        let esito = if self.avversario_rilevato {
+            "avversario rilevato"
+        } else if self.completata() {
+            "completata"
+        } else {
+            "round esauriti"
+        };
+        println!("Esito                                 : {:>9}", esito);
+        println!("Round di trasmissione                 : {:>9}", self.round);
+        println!("Blocchi post-elaborati                : {:>9}", self.blocchi);
+        println!("Impulsi spediti                       : {:>9}", self.fotoni);
+        println!("Bit setacciati accumulati             : {:>9}", self.bit_grezzi);
+        println!("Bit rivelati in riconciliazione       : {:>9}", self.bit_rivelati);
+        println!("Bit chiave finale                     : {:>9}", self.chiave_scrittore.len());
+        println!("Tasso di chiave (bit/impulso)         : {:>13.6}", self.tasso_chiave());
+        println!("Resa sui bit setacciati               : {:>12.2}%", self.resa_setaccio() * 100.0);
+        if self.completata() {
+            println!("Chiavi identiche                      : {:>9}", if self.chiave_scrittore == self.chiave_lettore { "sì" } else { "no" });
+            println!("Chiave: {:?}", self.chiave_scrittore);
+        }
+    }
+}
+
+/// Genera una chiave finale di `bit_obiettivo` bit con al più `round_max` round di trasmissione
+pub fn esegui_chiave_obiettivo(collegamento: &LinkModel, bit_obiettivo: usize, round_max: usize) -> RisultatoChiaveObiettivo {
+    imposta_verboso(false);
+    let mut risultato = RisultatoChiaveObiettivo { bit_obiettivo, ..Default::default() };
+    let mut resa = RESA_INIZIALE;
+    let mut accumulo: Option<(Writer, Reader, bool)> = None;
+
+    while risultato.chiave_scrittore.len() < bit_obiettivo && risultato.round < round_max {
+        let sessione = esegui_trasmissione(collegamento);
+        risultato.round += 1;
+        risultato.fotoni += sessione.scrittore.messaggio_quantistico.len();
+        risultato.bit_grezzi += sessione.scrittore.chiave_grezza.len();
+        let allarme = sessione.canale_pubblico.allarme_rivelatore;
+        let (mut scrittore, mut lettore, allarme) = match accumulo.take() {
+            None => (sessione.scrittore, sessione.lettore, allarme),
+            Some((mut scrittore, mut lettore, allarme_precedente)) => {
+                scrittore.accoda_round(sessione.scrittore);
+                lettore.accoda_round(sessione.lettore);
+                (scrittore, lettore, allarme || allarme_precedente)
+            }
+        };
+
+        // Si continua ad accumulare finché la chiave grezza non basta per i bit mancanti
+        // (gli allarmi sono invece gestiti subito dalla post-elaborazione)
+        let mancanti = bit_obiettivo - risultato.chiave_scrittore.len();
+        let ultimo_round = risultato.round == round_max;
+        if (scrittore.chiave_grezza.len() as f64) * resa < mancanti as f64 && !allarme && scrittore.sonde_rilevate == 0 && !ultimo_round {
+            accumulo = Some((scrittore, lettore, allarme));
+            continue;
+        }
+
+        let mut canale_pubblico = PublicChannel::new();
+        if allarme {
+            canale_pubblico.segnala_allarme_rivelatore();
+        }
+        let bit_grezzi_blocco = scrittore.chiave_grezza.len();
+        let riconciliazione = post_elaborazione(&mut scrittore, &mut lettore, &mut canale_pubblico);
+        risultato.blocchi += 1;
+        risultato.bit_test += scrittore.test_avversario.len();
+        risultato.errori_test += canale_pubblico.errori_test;
+        risultato.bit_rivelati += riconciliazione.map_or(0, |e| e.bit_rivelati);
+        if canale_pubblico.avversario_rilevato {
+            risultato.avversario_rilevato = true;
+            break;
+        }
+        // Resa osservata del blocco; un blocco scartato (nessun bit sicuro, riconciliazione interrotta o tag
+        // di verifica diversi) dimezza la resa e raddoppia il blocco successivo
+        if canale_pubblico.chiave_simmetrica_ok && !scrittore.chiave_simmetrica.is_empty() {
+            resa = scrittore.chiave_simmetrica.len() as f64 / bit_grezzi_blocco as f64;
+            risultato.chiave_scrittore.estendi(&scrittore.chiave_simmetrica);
+            risultato.chiave_lettore.estendi(&lettore.chiave_simmetrica);
+        } else {
+            resa /= 2.0;
+        }
+    }
+
+    risultato.chiave_scrittore.truncate(bit_obiettivo);
+    risultato.chiave_lettore.truncate(bit_obiettivo);
+    risultato
+}
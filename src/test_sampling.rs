@@ -0,0 +1,35 @@
+use crate::bit_vector::VettoreBit;
+use crate::config::{base_maggioritaria, basi_simmetriche, bit_da_base};
+
+// Selezione dei bit della chiave grezza da sacrificare per il test di presenza avversario.
+// Scrittore e lettore conoscono le basi di tutti i bit selezionati (sono pubbliche) e
+// calcolano quindi la stessa selezione in modo indipendente.
+
+/// Per ogni bit della chiave grezza indica se è usato per il test.
+/// - basi simmetriche (o basi non disponibili, es. SARG04): un bit ogni 8 partendo dal primo
+/// - basi asimmetriche: tutti i bit della base minoritaria e un bit ogni 8 della base maggioritaria,
+///   così il QBER è stimato separatamente per ciascuna base e la chiave usa quasi solo la base maggioritaria
+pub fn selezione_bit_test(basi_grezze: &VettoreBit, lunghezza: usize) -> VettoreBit {
+    if basi_simmetriche() || basi_grezze.len() != lunghezza {
+        return (0..lunghezza).map(|i: usize| i.is_multiple_of(8)).collect();
+    }
+    let maggioritaria = bit_da_base(base_maggioritaria()) == 1;
+    let mut k = 0usize; // posizione tra i bit della base maggioritaria
+    basi_grezze
+        .iter()
+        .map(|base| {
+            if base != maggioritaria {
+                true
+            } else {
+                let in_test = k.is_multiple_of(8);
+                k += 1;
+                in_test
+            }
+        })
+        .collect()
+}
+
+/// Separa la chiave grezza nei bit di test e nei bit che restano per la chiave simmetrica
+pub fn separa_test_e_chiave(chiave_grezza: &VettoreBit, selezione: &VettoreBit) -> (VettoreBit, VettoreBit) {
+    (chiave_grezza.seleziona(selezione), chiave_grezza.seleziona(&selezione.negato()))
+}
@@ -0,0 +1,24 @@
+use crate::config::{attenuazione_isolatore_db, fotoni_sonda, FRAZIONE_MONITOR, RIFLESSIONE_DISPOSITIVO_DB};
+
+// Attacco "cavallo di Troia" sull'apparato dello scrittore.
+// L'avversario inietta nel modulatore un impulso intenso di `FOTONI_SONDA` fotoni: la luce riflessa
+// attraversa il modulatore (e l'isolatore) due volte e porta con sé la base scelta dallo scrittore.
+// Contromisure: un isolatore ottico, che attenua la sonda a ogni passaggio, e un rivelatore di monitor
+// che preleva una frazione della luce in ingresso allo scrittore.
+
+/// Numero medio di fotoni riflessi verso l'avversario: sonda attenuata da riflessione e doppio passaggio nell'isolatore
+pub fn fotoni_riflessi() -> f64 {
+    fotoni_sonda() * 10f64.powf(-(RIFLESSIONE_DISPOSITIVO_DB + 2.0 * attenuazione_isolatore_db()) / 10.0)
+}
+
+/// Probabilità che l'avversario legga la base di un impulso: almeno un fotone riflesso (statistica poissoniana)
+pub fn prob_lettura_base() -> f64 {
+    1.0 - (-fotoni_riflessi()).exp()
+}
+
+/// Probabilità che il monitor dello scrittore rilevi la sonda: almeno un fotone nella frazione prelevata
+/// dopo un passaggio nell'isolatore
+pub fn prob_rilevazione_monitor() -> f64 {
+    let fotoni_monitor = fotoni_sonda() * 10f64.powf(-attenuazione_isolatore_db() / 10.0) * FRAZIONE_MONITOR;
+    1.0 - (-fotoni_monitor).exp()
+}
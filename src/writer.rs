@@ -1,116 +1,366 @@
 use rand::Rng;
-use crate::config::{lung_msg, POL_X, POL_Z};
+use crate::bit_vector::{SequenzaFotoni, VettoreBit};
+use crate::config::{
+    attiva_chiave_finita, attiva_decoy, attiva_monitor_scrittore, attiva_sorgente_laser, base_casuale, lung_msg, mu,
+    riconciliazione, soglia_qber, TipoRiconciliazione, POL_Z,
+};
+use crate::decoy::{stima_parametri, ClasseIntensita, StatisticheDecoy, StimaDecoy};
+use crate::entangled_source::EntangledSource;
+use crate::finite_key::{stima_da_config, StimaChiaveFinita};
+use crate::key_verification::TagVerifica;
+use crate::laser_source::LaserSource;
+use crate::privacy_amplification::{comprimi_chiave, genera_seme};
 use crate::public_channel::PublicChannel;
 use crate::quantum_channel::QuantumChannel;
+use crate::test_sampling::{selezione_bit_test, separa_test_e_chiave};
+use crate::trojan_horse::{prob_lettura_base, prob_rilevazione_monitor};
+use crate::session_rng::generatore;
 
 /// Scrittore
 /// Genera un messaggio quantistico e gestisce la pubblicazione e la selezione delle chiavi.
 pub struct Writer {
-    pub messaggio_quantistico: Vec<(char, u8)>, // vettore di (polarizzazione, valore)
-    pub chiave_grezza: Vec<u8>,
-    pub chiave_simmetrica: Vec<u8>,
-    pub test_avversario: Vec<u8>,
+    pub messaggio_quantistico: SequenzaFotoni, // sequenza di (polarizzazione, valore)
+    pub chiave_grezza: VettoreBit,
+    pub basi_chiave_grezza: VettoreBit, // base di ogni bit della chiave grezza, Z -> 0, X -> 1 (vuoto in SARG04)
+    pub slot_chiave_grezza: VettoreBit, // true per gli slot da cui proviene un bit della chiave grezza
+    pub selezione_test: VettoreBit,     // true per i bit della chiave grezza usati per il test
+    pub chiave_simmetrica: VettoreBit,
+    pub test_avversario: VettoreBit,
+    pub sorgente_laser: Option<LaserSource>, // None: sorgente ideale a singolo fotone
+    pub numero_fotoni_impulsi: Vec<u32>,     // fotoni emessi in ciascun impulso (vuoto con la sorgente ideale)
+    pub rilevazioni_lettore: VettoreBit,     // slot rilevati dal lettore (letti dal canale pubblico)
+    // Decoy-state: classe di intensità di ogni impulso e bit esca/vuoto selezionati (usati solo per la stima)
+    pub classi_intensita: Vec<ClasseIntensita>,
+    pub bit_stima_decoy: Vec<(ClasseIntensita, u8)>,
+    pub statistiche_decoy: Option<StatisticheDecoy>,
+    pub stima_decoy: Option<StimaDecoy>,
+    pub stima_chiave_finita: Option<StimaChiaveFinita>,
+    pub sonde_rilevate: usize, // impulsi in cui il monitor ha rilevato luce iniettata (cavallo di Troia)
 }
 
 impl Writer {
     pub fn new() -> Self {
         Self {
-            messaggio_quantistico: Vec::with_capacity(lung_msg()),
-            chiave_grezza: Vec::new(),
-            chiave_simmetrica: Vec::new(),
-            test_avversario: Vec::new(),
+            messaggio_quantistico: SequenzaFotoni::con_capacita(lung_msg()),
+            chiave_grezza: VettoreBit::new(),
+            basi_chiave_grezza: VettoreBit::new(),
+            slot_chiave_grezza: VettoreBit::new(),
+            selezione_test: VettoreBit::new(),
+            chiave_simmetrica: VettoreBit::new(),
+            test_avversario: VettoreBit::new(),
+            sorgente_laser: if attiva_sorgente_laser() || attiva_decoy() { Some(LaserSource::new(mu())) } else { None },
+            numero_fotoni_impulsi: Vec::new(),
+            rilevazioni_lettore: VettoreBit::new(),
+            classi_intensita: Vec::new(),
+            bit_stima_decoy: Vec::new(),
+            statistiche_decoy: None,
+            stima_decoy: None,
+            stima_chiave_finita: None,
+            sonde_rilevate: 0,
         }
     }
 
-    /// Inizializzazione: genera LUNG_MSG fotoni con polarizzazione (Z/X) e valore (0/1) casuali
+    /// Inizializzazione: genera LUNG_MSG fotoni con polarizzazione (Z/X, secondo `PROB_BASE_Z`) e valore (0/1) casuali.
+    /// Con il decoy-state sceglie anche la classe di intensità di ogni impulso (tenuta segreta fino a fine trasmissione).
     pub fn inizializzazione(&mut self) {
-        println!("[Scrittore]: Inizializzazione e scelta messaggio quantistico");
-        let mut rng = rand::thread_rng();
+        traccia!("[Scrittore]: Inizializzazione e scelta messaggio quantistico");
+        let mut rng = generatore();
         for _ in 0..lung_msg() {
-            let pol = if rng.gen_bool(0.5) { POL_Z } else { POL_X };
+            let pol = base_casuale();
             let val = rng.gen_range(0..=1);
             self.messaggio_quantistico.push((pol, val));
+            if attiva_decoy() {
+                self.classi_intensita.push(ClasseIntensita::casuale());
+            }
         }
     }
 
-    /// Scrive sul canale quantistico un fotone alla volta e attiva Fotone_IN
-    pub fn scrivi_su_canale_quantistico(&self, q: &mut QuantumChannel, indice: usize) {
+    /// Scrive sul canale quantistico un fotone alla volta e attiva Fotone_IN.
+    /// Con la sorgente laser il fotone diventa un impulso con numero di fotoni poissoniano.
+    pub fn scrivi_su_canale_quantistico(&mut self, q: &mut QuantumChannel, indice: usize) {
         let (pol, val) = self.messaggio_quantistico[indice];
-        q.spedizione_fotone(pol, val);
+        match (&self.sorgente_laser, self.classi_intensita.get(indice)) {
+            (Some(laser), Some(classe)) => q.spedizione_impulso(pol, val, laser.numero_fotoni_intensita(classe.intensita())),
+            (Some(laser), None) => q.spedizione_impulso(pol, val, laser.numero_fotoni_impulso()),
+            (None, _) => q.spedizione_fotone(pol, val),
+        }
+        // Con la sorgente ideale ogni impulso ha un fotone: il conteggio serve solo alle statistiche del laser
+        if self.sorgente_laser.is_some() {
+            self.numero_fotoni_impulsi.push(q.numero_fotoni);
+        }
         q.set_fotone_in();
     }
 
+    /// Accumulo di più round: accoda messaggio, rilevazioni e chiave grezza di un round successivo
+    /// (gli slot della chiave grezza seguono quelli già accumulati)
+    pub fn accoda_round(&mut self, altro: Writer) {
+        self.messaggio_quantistico.estendi(&altro.messaggio_quantistico);
+        self.chiave_grezza.estendi(&altro.chiave_grezza);
+        self.basi_chiave_grezza.estendi(&altro.basi_chiave_grezza);
+        self.slot_chiave_grezza.estendi(&altro.slot_chiave_grezza);
+        self.numero_fotoni_impulsi.extend(altro.numero_fotoni_impulsi);
+        self.rilevazioni_lettore.estendi(&altro.rilevazioni_lettore);
+        self.classi_intensita.extend(altro.classi_intensita);
+        self.bit_stima_decoy.extend(altro.bit_stima_decoy);
+        self.sonde_rilevate += altro.sonde_rilevate;
+    }
+
+    /// Cavallo di Troia: risposta dell'apparato alla sonda iniettata durante la preparazione dell'impulso `indice`.
+    /// Il monitor (se presente) può rilevare la luce in ingresso; la luce riflessa rivela la base
+    /// all'avversario con probabilità dipendente da sonda, riflessione e isolatore.
+    pub fn risposta_sonda(&mut self, indice: usize) -> Option<char> {
+        let mut rng = generatore();
+        if attiva_monitor_scrittore() && rng.gen_bool(prob_rilevazione_monitor()) {
+            self.sonde_rilevate += 1;
+        }
+        if rng.gen_bool(prob_lettura_base()) { Some(self.messaggio_quantistico[indice].0) } else { None }
+    }
+
+    /// BBM92: misura il proprio fotone della coppia in una base casuale (Z/X, secondo `PROB_BASE_Z`) e resetta Fotone_IN.
+    /// Il risultato è registrato in `messaggio_quantistico` come se fosse il fotone preparato in BB84,
+    /// così pubblicazione delle polarizzazioni e selezione della chiave grezza restano invariate.
+    pub fn misura_fotone_sorgente(
+        &mut self,
+        s: &mut EntangledSource,
+        q: &mut QuantumChannel,
+        q_lettore: &mut QuantumChannel,
+    ) {
+        let pol = base_casuale();
+        let val = s.misura_primo_fotone(q, q_lettore, pol);
+        self.messaggio_quantistico.push((pol, val));
+        q.set_fotone_out();
+    }
+
     /// Pubblica la sequenza delle polarizzazioni utilizzate sul canale pubblico
     pub fn pubblicazione_polarizzazione(&self, p: &mut PublicChannel) {
-        let polarizzazioni: Vec<char> = self
+        p.pubblica_polarizzazioni(self.messaggio_quantistico.basi.clone());
+    }
+
+    /// SARG04: al posto della base pubblica, per ogni fotone, una coppia di stati non ortogonali
+    /// (uno in base Z e uno in base X) che contiene lo stato spedito.
+    /// L'altro stato della coppia è scelto a caso tra i due della base opposta.
+    pub fn pubblicazione_coppie_stati(&self, p: &mut PublicChannel) {
+        let mut rng = generatore();
+        let coppie: Vec<(u8, u8)> = self
             .messaggio_quantistico
             .iter()
-            .map(|(pol, _)| *pol)
+            .map(|(pol, val)| {
+                let altro: u8 = rng.gen_range(0..=1);
+                if pol == POL_Z { (val, altro) } else { (altro, val) }
+            })
             .collect();
-        p.pubblica_polarizzazioni(polarizzazioni.clone());
+        p.pubblica_coppie_stati(coppie);
+    }
+
+    /// SARG04: selezione chiave grezza dagli esiti conclusivi del lettore sugli slot rilevati.
+    /// Il bit è la base dello stato spedito (Z -> 0, X -> 1): il setaccio opera sulle parole delle basi.
+    pub fn selezione_chiave_grezza_sarg04(&mut self, p: &mut PublicChannel) {
+        self.rilevazioni_lettore.clone_from(p.leggi_sequenza_rilevazioni());
+        self.slot_chiave_grezza = p.leggi_sequenza_ricezione().and(&self.rilevazioni_lettore);
+        self.chiave_grezza = self.messaggio_quantistico.basi.seleziona(&self.slot_chiave_grezza);
     }
 
-    /// Selezione chiave grezza a partire dall'esito letture
+    /// Decoy-state: pubblica le classi di intensità dopo la fine della trasmissione
+    pub fn pubblicazione_classi_intensita(&self, p: &mut PublicChannel) {
+        p.pubblica_classi_intensita(self.classi_intensita.clone());
+    }
+
+    /// Selezione chiave grezza a partire dall'esito letture: solo slot rilevati dal lettore
+    /// e con polarizzazione coincidente.
+    /// Con il decoy-state solo gli impulsi segnale formano la chiave; i bit esca e vuoto
+    /// sono tenuti da parte per la stima dei parametri.
     pub fn selezione_chiave_grezza(&mut self, p: &mut PublicChannel) {
-        let esito = p.leggi_sequenza_ricezione();
-        self.chiave_grezza.clear();
-        for (i, ok) in esito.iter().enumerate() {
-            if *ok {
-                let (_, val) = self.messaggio_quantistico[i];
-                self.chiave_grezza.push(val);
+        self.rilevazioni_lettore.clone_from(p.leggi_sequenza_rilevazioni());
+        let mut selezionati = p.leggi_sequenza_ricezione().and(&self.rilevazioni_lettore);
+        self.bit_stima_decoy.clear();
+        if !self.classi_intensita.is_empty() {
+            let segnale: VettoreBit = self.classi_intensita.iter().map(|&c| c == ClasseIntensita::Segnale).collect();
+            for i in selezionati.and(&segnale.negato()).uni() {
+                self.bit_stima_decoy.push((self.classi_intensita[i], self.messaggio_quantistico[i].1));
             }
+            selezionati = selezionati.and(&segnale);
         }
+        self.chiave_grezza = self.messaggio_quantistico.valori.seleziona(&selezionati);
+        self.basi_chiave_grezza = self.messaggio_quantistico.basi.seleziona(&selezionati);
+        self.slot_chiave_grezza = selezionati;
     }
 
-    /// Selezione bit di test, verifica presenza avversario e definizione chiave finale
-    pub fn selezione_test_e_chiave_finale(&mut self, p: &mut PublicChannel) {
-        // Prende un bit ogni 8 dalla chiave grezza, partendo dal primo
-        self.test_avversario.clear();
-        let mut idx = 0usize;
-        while idx < self.chiave_grezza.len() {
-            self.test_avversario.push(self.chiave_grezza[idx]);
-            idx += 8;
+    /// Selezione bit di test e verifica presenza avversario.
+    /// Senza riconciliazione un solo errore nei bit di test scarta la chiave; con la riconciliazione attiva
+    /// la chiave è scartata solo se il QBER di test supera `SOGLIA_QBER`. Se il test è superato la chiave
+    /// simmetrica è la chiave grezza senza i bit di test, il numero di errori è pubblicato e ritorna `true`.
+    pub fn selezione_test_e_verifica(&mut self, p: &mut PublicChannel) -> bool {
+        // Allarme del rivelatore del lettore: la sessione è compromessa indipendentemente dal test
+        if p.allarme_rivelatore {
+            traccia!("[Scrittore]: Allarme accecamento del rivelatore del lettore. Chiave scartata");
+            p.segnala_avversario_rilevato();
+            p.processo_terminato();
+            return false;
         }
+        // Allarme del monitor dello scrittore: luce iniettata nel modulatore
+        if self.sonde_rilevate > 0 {
+            traccia!("[Scrittore]: Monitor: rilevata luce iniettata in {} impulsi. Chiave scartata", self.sonde_rilevate);
+            p.segnala_avversario_rilevato();
+            p.processo_terminato();
+            return false;
+        }
+
+        // Bit di test: uno ogni 8 partendo dal primo, oppure con basi asimmetriche
+        // tutti quelli della base minoritaria più uno ogni 8 della base maggioritaria
+        self.selezione_test = selezione_bit_test(&self.basi_chiave_grezza, self.chiave_grezza.len());
+        let (test, chiave) = separa_test_e_chiave(&self.chiave_grezza, &self.selezione_test);
+        self.test_avversario = test;
 
         // Attende che il lettore abbia scritto il suo test e lo legge
         // (nella nostra orchestrazione, il lettore scrive prima di arrivare qui)
-        if p.test_avversario_pronto {
-            let test_lettore = p.leggi_test_avversario();
-            // Confronto
-            let mut errore = false;
-            for (a, b) in self.test_avversario.iter().zip(test_lettore.iter()) {
-                if a != b { errore = true; break; }
-            }
-            if errore || self.test_avversario.len() != test_lettore.len() {
-                println!("[Scrittore]: Test presenza avversario positivo. Chiave scartata");
-                // Scrive sul terminale quanti bit sono stati confrontati e quanti sono risultati errati
-                let mut errori_count = 0;
-                for (a, b) in self.test_avversario.iter().zip(test_lettore.iter()) {
-                    if a != b { errori_count += 1; }
-                }
-                if self.test_avversario.len() != test_lettore.len() {
-                    errori_count += (self.test_avversario.len() as isize - test_lettore.len() as isize).unsigned_abs();
-                }
-                println!("[Scrittore]: Test confrontato su {} bit, con {} errori", self.test_avversario.len(), errori_count);    
-                // Processo terminato senza chiave valida
-                p.processo_terminato();
-            } else {
-                println!("[Scrittore]: Test presenza avversario negativo");
-                // Costruisce la chiave simmetrica rimuovendo i bit usati per il test (uno ogni 8)
-                self.chiave_simmetrica.clear();
-                for (i, b) in self.chiave_grezza.iter().enumerate() {
-                    if i % 8 != 0 { // i=0,8,16... sono usati per il test
-                        self.chiave_simmetrica.push(*b);
-                    }
-                }
-                println!("[Scrittore]: Chiave simmetrica definita: {:?}", self.chiave_simmetrica);
-                p.chiave_simmetrica_ok();
-                p.processo_terminato();
+        if !p.test_avversario_pronto {
+            // In casi limite, se non pronto, consideriamo test non disponibile
+            traccia!("[Scrittore]: Test avversario non pronto: processo terminato senza chiave");
+            p.processo_terminato();
+            return false;
+        }
+        let test_lettore = p.leggi_test_avversario();
+        // Confronto (XOR e conteggio per parole)
+        let mut errori_count = self.test_avversario.differenze(test_lettore);
+        let lunghezza_lettore = test_lettore.len();
+        let lunghezze_diverse = self.test_avversario.len() != lunghezza_lettore;
+        let qber_test = if self.test_avversario.is_empty() { 0.0 } else { errori_count as f64 / self.test_avversario.len() as f64 };
+        let test_superato = match riconciliazione() {
+            TipoRiconciliazione::Nessuna => errori_count == 0,
+            _ => qber_test <= soglia_qber(),
+        };
+        if !test_superato || lunghezze_diverse {
+            traccia!("[Scrittore]: Test presenza avversario positivo. Chiave scartata");
+            // Scrive sul terminale quanti bit sono stati confrontati e quanti sono risultati errati
+            if lunghezze_diverse {
+                errori_count += (self.test_avversario.len() as isize - lunghezza_lettore as isize).unsigned_abs();
             }
+            traccia!("[Scrittore]: Test confrontato su {} bit, con {} errori", self.test_avversario.len(), errori_count);
+            // Processo terminato senza chiave valida
+            p.segnala_avversario_rilevato();
+            p.processo_terminato();
+            return false;
+        }
+
+        if errori_count == 0 {
+            traccia!("[Scrittore]: Test presenza avversario negativo");
         } else {
-            // In casi limite, se non pronto, consideriamo test non disponibile
-            println!("[Scrittore]: Test avversario non pronto: processo terminato senza chiave");
+            traccia!("[Scrittore]: Test con QBER {:.2}% entro la soglia: si procede alla riconciliazione", qber_test * 100.0);
+        }
+        // La chiave simmetrica è la chiave grezza senza i bit usati per il test
+        self.chiave_simmetrica = chiave;
+        p.pubblica_errori_test(errori_count);
+        true
+    }
+
+    /// Verifica della chiave (dopo l'eventuale riconciliazione): pubblica seme e tag di hash universale
+    pub fn pubblicazione_tag_verifica(&self, p: &mut PublicChannel) {
+        match TagVerifica::genera(&self.chiave_simmetrica) {
+            Some(tag) => {
+                traccia!("[Scrittore]: Pubblicazione tag di verifica della chiave ({} bit)", tag.tag.len());
+                p.pubblica_tag_verifica(tag);
+            }
+            None => traccia!("[Scrittore]: Chiave di {} bit troppo corta per il tag di verifica", self.chiave_simmetrica.len()),
+        }
+    }
+
+    /// Riconciliazione interrotta (bit rivelati oltre il limite ammesso): la chiave è scartata e il processo termina
+    pub fn scarta_chiave_non_riconciliata(&mut self, p: &mut PublicChannel) {
+        traccia!("[Scrittore]: Riconciliazione interrotta oltre il limite di bit rivelati. Chiave scartata");
+        self.chiave_simmetrica.clear();
+        p.processo_terminato();
+    }
+
+    /// Definizione della chiave finale dopo il test, l'eventuale riconciliazione e la verifica dei tag:
+    /// eventuale amplificazione della privacy decoy-state o a chiave finita, quindi termina il processo
+    pub fn definizione_chiave_finale(&mut self, p: &mut PublicChannel) {
+        if p.tag_verifica.is_none() {
+            traccia!("[Scrittore]: Chiave non verificabile. Chiave scartata");
+            self.chiave_simmetrica.clear();
+            p.processo_terminato();
+            return;
+        }
+        // Tag diversi: errori residui dopo la riconciliazione, non una prova della presenza dell'avversario
+        // (il disturbo è già misurato dal test): la chiave è scartata senza segnalare una rilevazione
+        if p.chiave_verificata != Some(true) {
+            traccia!("[Scrittore]: Tag di verifica diversi: chiavi non identiche. Chiave scartata");
+            self.chiave_simmetrica.clear();
+            p.processo_terminato();
+            return;
+        }
+        if attiva_decoy() && !self.stima_decoy_e_amplificazione(p) {
+            traccia!("[Scrittore]: Stima decoy-state senza bit sicuri. Chiave scartata");
+            self.chiave_simmetrica.clear();
             p.processo_terminato();
+            return;
         }
+        if attiva_chiave_finita() && !self.chiave_finita_e_amplificazione(p) {
+            traccia!("[Scrittore]: Analisi a chiave finita senza bit sicuri. Chiave scartata");
+            self.chiave_simmetrica.clear();
+            p.processo_terminato();
+            return;
+        }
+        traccia!("[Scrittore]: Chiave simmetrica definita: {:?}", self.chiave_simmetrica);
+        p.chiave_simmetrica_ok();
+        p.processo_terminato();
+    }
+
+    /// Decoy-state: completa le statistiche per classe (impulsi, rilevazioni pubblicate dal lettore, errori sui bit
+    /// esca/vuoto rivelati e sui bit di test), le pubblica, stima la lunghezza sicura della chiave e
+    /// comprime la chiave simmetrica con una matrice di Toeplitz il cui seme è pubblicato.
+    /// Ritorna `false` se la stima non lascia alcun bit sicuro.
+    fn stima_decoy_e_amplificazione(&mut self, p: &mut PublicChannel) -> bool {
+        let mut statistiche = StatisticheDecoy::default();
+        for (classe, rilevato) in self.classi_intensita.iter().zip(self.rilevazioni_lettore.iter()) {
+            statistiche.impulsi[classe.indice()] += 1;
+            if rilevato { statistiche.rilevati[classe.indice()] += 1; }
+        }
+        for ((classe, b_scrittore), b_lettore) in self.bit_stima_decoy.iter().zip(p.bit_stima_decoy.iter()) {
+            statistiche.bit_confrontati[classe.indice()] += 1;
+            if b_scrittore != b_lettore { statistiche.errori[classe.indice()] += 1; }
+        }
+        // Gli impulsi segnale usano i bit di test (errori pubblicati dopo il confronto)
+        statistiche.bit_confrontati[ClasseIntensita::Segnale.indice()] = self.test_avversario.len();
+        statistiche.errori[ClasseIntensita::Segnale.indice()] = p.errori_test;
+        p.pubblica_statistiche_decoy(statistiche.clone());
+
+        let stima = stima_parametri(&statistiche, self.chiave_simmetrica.len());
+        // La stima non conta i bit rivelati dal tag di verifica
+        let lunghezza = stima.lunghezza_chiave.saturating_sub(p.bit_tag_verifica());
+        traccia!(
+            "[Scrittore]: Stima decoy-state: Y1 >= {:.3}, e1 <= {:.3}, chiave sicura {} bit su {}",
+            stima.y1_l, stima.e1_u, lunghezza, self.chiave_simmetrica.len()
+        );
+        self.statistiche_decoy = Some(statistiche);
+        self.stima_decoy = Some(stima);
+        if lunghezza == 0 {
+            return false;
+        }
+
+        let seme = genera_seme(self.chiave_simmetrica.len(), lunghezza);
+        self.chiave_simmetrica = comprimi_chiave(&self.chiave_simmetrica, lunghezza, &seme);
+        p.pubblica_seme_amplificazione(seme);
+        true
+    }
+
+    /// Analisi a chiave finita: dimensiona la chiave finale dal campione di test (limite statistico
+    /// sull'errore di fase, perdita di riconciliazione e parametri ε; il tag è nel termine log2(1/ε_cor)) e la comprime con una matrice
+    /// di Toeplitz il cui seme è pubblicato. Ritorna `false` se non resta alcun bit sicuro.
+    fn chiave_finita_e_amplificazione(&mut self, p: &mut PublicChannel) -> bool {
+        let stima = stima_da_config(self.chiave_simmetrica.len(), self.test_avversario.len(), p.errori_test, p.bit_riconciliazione);
+        let lunghezza = stima.lunghezza_chiave;
+        traccia!(
+            "[Scrittore]: Analisi a chiave finita: QBER {:.3} + {:.3}, chiave sicura {} bit su {}",
+            stima.qber_osservato, stima.deviazione, lunghezza, self.chiave_simmetrica.len()
+        );
+        self.stima_chiave_finita = Some(stima);
+        if lunghezza == 0 {
+            return false;
+        }
+
+        let seme = genera_seme(self.chiave_simmetrica.len(), lunghezza);
+        self.chiave_simmetrica = comprimi_chiave(&self.chiave_simmetrica, lunghezza, &seme);
+        p.pubblica_seme_amplificazione(seme);
+        true
     }
 }